@@ -0,0 +1,549 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::fs;
+use uuid::Uuid;
+use crate::disk::disk_space;
+use crate::errors::YtdlpError;
+use crate::formats::{estimate_size, VideoInfo};
+use crate::probe::{probe_media, validate_media, MediaInfo};
+use crate::process::CommandLimits;
+use crate::transcode::{extract_audio, strip_audio};
+use crate::youtube::{extract_video_id, get_video_info, get_format_hierarchy, execute_ytdlp};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MediaType {
+    Audio,
+    Video,
+    Merge,
+}
+
+impl MediaType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MediaType::Audio => "audio",
+            MediaType::Video => "video",
+            MediaType::Merge => "merge",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            MediaType::Audio => ".mp3",
+            MediaType::Video | MediaType::Merge => ".mp4",
+        }
+    }
+
+    pub fn derivable_from(&self) -> &'static [MediaType] {
+        match self {
+            MediaType::Audio => &[MediaType::Merge, MediaType::Video],
+            MediaType::Video => &[MediaType::Merge],
+            MediaType::Merge => &[],
+        }
+    }
+}
+
+impl FromStr for MediaType {
+    type Err = DownloadError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "audio" => Ok(MediaType::Audio),
+            "video" => Ok(MediaType::Video),
+            "merge" => Ok(MediaType::Merge),
+            _ => Err(DownloadError::InvalidMediaType),
+        }
+    }
+}
+
+impl fmt::Display for MediaType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloadRequest {
+    pub url: String,
+    pub media_type: MediaType,
+    pub output_dir: PathBuf,
+    pub file_stem: String,
+}
+
+impl DownloadRequest {
+    pub fn new(url: impl Into<String>, media_type: MediaType, output_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            url: url.into(),
+            media_type,
+            output_dir: output_dir.into(),
+            file_stem: Uuid::new_v4().to_string(),
+        }
+    }
+
+    pub fn file_stem(mut self, file_stem: impl Into<String>) -> Self {
+        self.file_stem = file_stem.into();
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloaderOptions {
+    pub concurrent_fragments: usize,
+    pub ffmpeg_path: String,
+    pub ffprobe_path: Option<String>,
+    pub duration_tolerance: f64,
+    pub max_video_duration: Option<i64>,
+    pub max_audio_duration: Option<i64>,
+    pub max_file_size: Option<u64>,
+    pub disk_space_margin: u64,
+    pub limits: CommandLimits,
+}
+
+impl Default for DownloaderOptions {
+    fn default() -> Self {
+        Self {
+            concurrent_fragments: 4,
+            ffmpeg_path: String::new(),
+            ffprobe_path: None,
+            duration_tolerance: 3.0,
+            max_video_duration: None,
+            max_audio_duration: None,
+            max_file_size: None,
+            disk_space_margin: 0,
+            limits: CommandLimits::default(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Progress {
+    Probing,
+    Downloading { attempt: usize, format: String },
+    Percent { attempt: usize, percent: f64 },
+    Output { attempt: usize, format: String, error: Option<YtdlpError>, stdout: String, stderr: String },
+    Processing,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AttemptOutcome {
+    Success,
+    MissingOutput,
+    InvalidOutput,
+    Skipped,
+    Failed(YtdlpError),
+}
+
+impl AttemptOutcome {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AttemptOutcome::Success => "success",
+            AttemptOutcome::MissingOutput => "missing_output",
+            AttemptOutcome::InvalidOutput => "invalid_output",
+            AttemptOutcome::Skipped => "skipped",
+            AttemptOutcome::Failed(_) => "failed",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct DownloadResult {
+    pub file_path: PathBuf,
+    pub format: String,
+    pub attempts: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+    pub media_info: Option<MediaInfo>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DownloadError {
+    InvalidUrl,
+    InvalidMediaType,
+    DurationExceeded { duration: f64, max: i64 },
+    FileTooLarge { size: u64, max: u64 },
+    InsufficientSpace { required: u64, available: u64 },
+    Ytdlp(YtdlpError),
+    AllFormatsFailed,
+    InvalidOutput,
+    Transcode,
+    Cancelled,
+}
+
+impl DownloadError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            DownloadError::InvalidUrl => "invalid_url",
+            DownloadError::InvalidMediaType => "invalid_media_type",
+            DownloadError::DurationExceeded { .. } => "duration_exceeded",
+            DownloadError::FileTooLarge { .. } => "file_too_large",
+            DownloadError::InsufficientSpace { .. } => "insufficient_disk_space",
+            DownloadError::Ytdlp(error) => error.code(),
+            DownloadError::AllFormatsFailed => "all_formats_failed",
+            DownloadError::InvalidOutput => "invalid_output",
+            DownloadError::Transcode => "transcode_failed",
+            DownloadError::Cancelled => "interrupted",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            DownloadError::InvalidUrl => "Invalid YouTube URL or ID",
+            DownloadError::InvalidMediaType => "Invalid media type. Allowed: audio, video, merge",
+            DownloadError::DurationExceeded { .. } => "Duration exceeds maximum",
+            DownloadError::FileTooLarge { .. } => "File size exceeds maximum",
+            DownloadError::InsufficientSpace { .. } => "Not enough free disk space on the server",
+            DownloadError::Ytdlp(error) => error.message(),
+            DownloadError::AllFormatsFailed => "All format attempts failed",
+            DownloadError::InvalidOutput => "Downloaded file failed validation",
+            DownloadError::Transcode => "Local transcoding failed",
+            DownloadError::Cancelled => "Download was cancelled",
+        }
+    }
+}
+
+impl fmt::Display for DownloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for DownloadError {}
+
+pub trait DownloadHooks: Send + Sync {
+    fn select_cookies(&self) -> Option<String> {
+        None
+    }
+
+    fn select_proxy(&self, _video_id: &str, _cookies: Option<&str>) -> Option<String> {
+        None
+    }
+
+    fn cookies_succeeded(&self, _cookies: &str) {}
+
+    fn cookies_failed(&self, _cookies: &str, _error: YtdlpError) {}
+
+    fn proxy_succeeded(&self, _proxy: &str) {}
+
+    fn proxy_failed(&self, _proxy: &str, _error: YtdlpError) {}
+
+    fn ytdlp_failed(&self, _error: YtdlpError) {}
+
+    fn attempt_finished(&self, _media_type: MediaType, _attempt: usize, _outcome: AttemptOutcome) {}
+
+    /// Called from the blocking thread pool, so implementations may walk the
+    /// filesystem synchronously.
+    fn reclaim_space(&self, _dir: &Path, _bytes: u64, _keep: Option<&Path>) -> u64 {
+        0
+    }
+
+    fn is_cancelled(&self) -> bool {
+        false
+    }
+}
+
+struct NoHooks;
+
+impl DownloadHooks for NoHooks {}
+
+#[derive(Clone)]
+pub struct Downloader {
+    options: DownloaderOptions,
+    hooks: Arc<dyn DownloadHooks>,
+}
+
+impl Downloader {
+    pub fn new(options: DownloaderOptions) -> Self {
+        Self {
+            options,
+            hooks: Arc::new(NoHooks),
+        }
+    }
+
+    pub fn with_hooks(mut self, hooks: Arc<dyn DownloadHooks>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    pub fn options(&self) -> &DownloaderOptions {
+        &self.options
+    }
+
+    async fn verify_output(&self, media_type: MediaType, path: &Path, expected_duration: Option<f64>) -> Result<Option<MediaInfo>, String> {
+        let ffprobe = match self.options.ffprobe_path.as_deref() {
+            Some(ffprobe) => ffprobe,
+            None => return Ok(None),
+        };
+
+        let info = match probe_media(ffprobe, path, &self.options.limits).await? {
+            Some(info) => info,
+            None => return Ok(None),
+        };
+        validate_media(media_type, &info, expected_duration, self.options.duration_tolerance)?;
+        Ok(Some(info))
+    }
+
+    async fn ensure_free_space(&self, dir: &Path, estimated_size: u64, keep: Option<&Path>) -> Result<(), DownloadError> {
+        let required = estimated_size.saturating_add(self.options.disk_space_margin);
+        let available = match disk_space(dir) {
+            Some(space) if space.available < required => space.available,
+            _ => return Ok(()),
+        };
+
+        let hooks = self.hooks.clone();
+        let (target, keep) = (dir.to_path_buf(), keep.map(Path::to_path_buf));
+        let reclaimed = tokio::task::spawn_blocking(move || hooks.reclaim_space(&target, required - available, keep.as_deref()))
+            .await
+            .unwrap_or(0);
+        let available = match reclaimed {
+            0 => available,
+            _ => disk_space(dir).map(|space| space.available).unwrap_or(available + reclaimed),
+        };
+        if available < required {
+            tracing::warn!(required, available, dir = %dir.display(), "not enough free disk space");
+            return Err(DownloadError::InsufficientSpace { required, available });
+        }
+
+        tracing::info!(reclaimed, available, "freed disk space by evicting cached files");
+        Ok(())
+    }
+
+    pub async fn derive<F>(&self, source: &Path, source_type: MediaType, request: &DownloadRequest, on_progress: F) -> Result<DownloadResult, DownloadError>
+    where
+        F: Fn(Progress),
+    {
+        let started = Instant::now();
+        if !request.media_type.derivable_from().contains(&source_type) {
+            return Err(DownloadError::Transcode);
+        }
+        if self.hooks.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+
+        let source_size = fs::metadata(source).await.map(|m| m.len()).unwrap_or(0);
+        self.ensure_free_space(&request.output_dir, source_size, Some(source)).await?;
+
+        on_progress(Progress::Processing);
+        let file_path = request.output_dir.join(format!("{}{}", request.file_stem, request.media_type.extension()));
+        let ffmpeg = &self.options.ffmpeg_path;
+
+        let result = match request.media_type {
+            MediaType::Audio => extract_audio(ffmpeg, source, &file_path, &self.options.limits).await,
+            _ => strip_audio(ffmpeg, source, &file_path, &self.options.limits).await,
+        };
+
+        if let Err(e) = result {
+            remove_partial_files(&request.output_dir, &request.file_stem).await;
+            if self.hooks.is_cancelled() {
+                return Err(DownloadError::Cancelled);
+            }
+            tracing::warn!(source = %source.display(), source_type = %source_type, error = %e, "deriving from cached rendition failed");
+            return Err(DownloadError::Transcode);
+        }
+
+        let media_info = match self.verify_output(request.media_type, &file_path, None).await {
+            Ok(info) => info,
+            Err(e) => {
+                tracing::warn!(source = %source.display(), source_type = %source_type, error = %e, "derived file failed validation");
+                remove_partial_files(&request.output_dir, &request.file_stem).await;
+                return Err(DownloadError::Transcode);
+            }
+        };
+
+        let bytes = fs::metadata(&file_path).await.map(|m| m.len()).unwrap_or(0);
+        tracing::info!(file = %file_path.display(), source_type = %source_type, elapsed_ms = started.elapsed().as_millis() as u64, "derived from cached rendition");
+        Ok(DownloadResult {
+            file_path,
+            format: format!("derived:{}", source_type),
+            attempts: 0,
+            bytes,
+            elapsed: started.elapsed(),
+            media_info,
+        })
+    }
+
+    pub async fn download<F>(&self, request: &DownloadRequest, on_progress: F) -> Result<DownloadResult, DownloadError>
+    where
+        F: Fn(Progress),
+    {
+        let started = Instant::now();
+        let hooks = self.hooks.as_ref();
+        let media_type = request.media_type;
+        let video_id = extract_video_id(&request.url).map_err(|_| DownloadError::InvalidUrl)?;
+        let dir = request.output_dir.to_string_lossy().to_string();
+        let stem = request.file_stem.as_str();
+
+        let mut cookies = hooks.select_cookies();
+        let mut proxy = hooks.select_proxy(&video_id, cookies.as_deref());
+
+        let mut video_info: Option<VideoInfo> = None;
+
+        on_progress(Progress::Probing);
+        match get_video_info(&request.url, cookies.as_deref(), proxy.as_deref(), &self.options.limits).await {
+            Ok(Some(info)) => {
+                let duration = info.duration;
+                let max_duration = match media_type {
+                    MediaType::Audio => self.options.max_audio_duration,
+                    _ => self.options.max_video_duration,
+                };
+
+                if let Some(max) = max_duration.filter(|max| duration > *max as f64) {
+                    tracing::warn!(duration, max_duration = max, "duration exceeds maximum");
+                    return Err(DownloadError::DurationExceeded { duration, max });
+                }
+
+                video_info = Some(info);
+            }
+            Ok(None) => {}
+            Err(failure) => {
+                let error = failure.error;
+                on_progress(Progress::Output { attempt: 0, format: "metadata".to_string(), error: Some(error), stdout: failure.stdout, stderr: failure.stderr });
+                if error.is_permanent() {
+                    hooks.ytdlp_failed(error);
+                    return Err(DownloadError::Ytdlp(error));
+                }
+            }
+        }
+
+        let expected_duration = video_info.as_ref().map(|info| info.duration);
+        let mut last_error: Option<YtdlpError> = None;
+        let mut invalid_output = false;
+        let mut oversized: Option<(u64, u64)> = None;
+        let mut space_error: Option<DownloadError> = None;
+
+        for (index, (format, ext, post_proc)) in get_format_hierarchy(media_type.as_str()).into_iter().enumerate() {
+            let attempt = index + 1;
+            if hooks.is_cancelled() {
+                remove_partial_files(&dir, stem).await;
+                return Err(DownloadError::Cancelled);
+            }
+
+            let estimate = video_info.as_ref().and_then(|info| estimate_size(format, info));
+            if let Some((size, max)) = estimate.zip(self.options.max_file_size).filter(|(size, max)| size > max) {
+                tracing::info!(attempt, format, estimated_size = size, max_file_size = max, "format exceeds maximum file size; skipping");
+                hooks.attempt_finished(media_type, attempt, AttemptOutcome::Skipped);
+                oversized = Some(oversized.map_or((size, max), |(smallest, max)| (smallest.min(size), max)));
+                continue;
+            }
+            if let Err(error) = self.ensure_free_space(&request.output_dir, estimate.unwrap_or(0), None).await {
+                hooks.attempt_finished(media_type, attempt, AttemptOutcome::Skipped);
+                space_error = Some(error);
+                continue;
+            }
+
+            on_progress(Progress::Downloading { attempt, format: format.to_string() });
+            tracing::debug!(attempt, format, estimated_size = estimate, "starting format attempt");
+            let output_template = format!("{}/{}.%(ext)s", dir, stem);
+
+            match execute_ytdlp(&request.url, format, &output_template, post_proc, self.options.concurrent_fragments, &self.options.ffmpeg_path, cookies.as_deref(), proxy.as_deref(), &self.options.limits, |percent| on_progress(Progress::Percent { attempt, percent })).await {
+                Ok(output) => {
+                    on_progress(Progress::Output {
+                        attempt,
+                        format: format.to_string(),
+                        error: None,
+                        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+                        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    });
+                    if let Some(cookie_file) = cookies.as_deref() {
+                        hooks.cookies_succeeded(cookie_file);
+                    }
+                    if let Some(proxy_url) = proxy.as_deref() {
+                        hooks.proxy_succeeded(proxy_url);
+                    }
+
+                    on_progress(Progress::Processing);
+
+                    let file_path = request.output_dir.join(format!("{}{}", stem, ext));
+                    match fs::metadata(&file_path).await {
+                        Ok(metadata) if metadata.is_file() => {
+                            let media_info = match self.verify_output(media_type, &file_path, expected_duration).await {
+                                Ok(info) => info,
+                                Err(e) => {
+                                    tracing::warn!(attempt, format, error = %e, "output failed validation; trying next format");
+                                    hooks.attempt_finished(media_type, attempt, AttemptOutcome::InvalidOutput);
+                                    remove_partial_files(&dir, stem).await;
+                                    invalid_output = true;
+                                    continue;
+                                }
+                            };
+
+                            hooks.attempt_finished(media_type, attempt, AttemptOutcome::Success);
+                            tracing::info!(file = %file_path.display(), elapsed_ms = started.elapsed().as_millis() as u64, "download completed");
+                            return Ok(DownloadResult {
+                                file_path,
+                                format: format.to_string(),
+                                attempts: attempt,
+                                bytes: metadata.len(),
+                                elapsed: started.elapsed(),
+                                media_info,
+                            });
+                        }
+                        _ => {
+                            hooks.attempt_finished(media_type, attempt, AttemptOutcome::MissingOutput);
+                            continue;
+                        }
+                    }
+                }
+                Err(failure) => {
+                    if hooks.is_cancelled() {
+                        remove_partial_files(&dir, stem).await;
+                        return Err(DownloadError::Cancelled);
+                    }
+
+                    let error = failure.error;
+                    on_progress(Progress::Output { attempt, format: format.to_string(), error: Some(error), stdout: failure.stdout, stderr: failure.stderr });
+                    last_error = Some(error);
+                    hooks.attempt_finished(media_type, attempt, AttemptOutcome::Failed(error));
+                    hooks.ytdlp_failed(error);
+
+                    if error.is_permanent() {
+                        tracing::warn!(error_code = error.code(), "error cannot be fixed by another format; stopping fallback");
+                        break;
+                    }
+
+                    let identity_in_use = cookies.is_some() || proxy.is_some();
+                    let mut rotated = false;
+                    if let Some(cookie_file) = cookies.clone().filter(|_| error.is_cookie_failure()) {
+                        hooks.cookies_failed(&cookie_file, error);
+                        cookies = hooks.select_cookies();
+                        rotated |= cookies.as_deref() != Some(cookie_file.as_str());
+                    }
+                    if let Some(proxy_url) = proxy.clone().filter(|_| error.is_proxy_failure()) {
+                        hooks.proxy_failed(&proxy_url, error);
+                        proxy = hooks.select_proxy(&video_id, cookies.as_deref());
+                        rotated |= proxy.as_deref() != Some(proxy_url.as_str());
+                    }
+
+                    if (error.is_cookie_failure() || error == YtdlpError::RateLimited) && identity_in_use && !rotated {
+                        tracing::warn!(error_code = error.code(), "no other cookies or proxies to try; stopping fallback");
+                        break;
+                    }
+                    continue;
+                }
+            }
+        }
+
+        remove_partial_files(&dir, stem).await;
+
+        match last_error {
+            Some(error) => Err(DownloadError::Ytdlp(error)),
+            None if invalid_output => Err(DownloadError::InvalidOutput),
+            None => match (space_error, oversized) {
+                (Some(error), _) => Err(error),
+                (None, Some((size, max))) => Err(DownloadError::FileTooLarge { size, max }),
+                (None, None) => Err(DownloadError::AllFormatsFailed),
+            },
+        }
+    }
+}
+
+pub async fn remove_partial_files(dir: impl AsRef<Path>, prefix: &str) {
+    if let Ok(mut entries) = fs::read_dir(dir).await {
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if entry.file_name().to_string_lossy().starts_with(prefix) {
+                let _ = fs::remove_file(entry.path()).await;
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::fmt;
+use crate::process::CommandTimeout;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum YtdlpError {
+    VideoUnavailable,
+    Private,
+    MembersOnly,
+    AgeRestricted,
+    GeoBlocked,
+    SignInRequired,
+    BotCheck,
+    LiveNotStarted,
+    CopyrightTakedown,
+    Forbidden,
+    RateLimited,
+    Network,
+    FormatUnavailable,
+    Ffmpeg,
+    Timeout,
+    Spawn,
+    Unknown,
+}
+
+impl YtdlpError {
+    pub fn from_stderr(stderr: &str) -> Self {
+        let error_lines: Vec<&str> = stderr.lines().filter(|line| line.trim_start().starts_with("ERROR:")).collect();
+        let lower = match error_lines.is_empty() {
+            true => stderr.to_lowercase(),
+            false => error_lines.join("\n").to_lowercase(),
+        };
+        let has = |patterns: &[&str]| patterns.iter().any(|p| lower.contains(p));
+
+        if has(&["private video", "this video is private"]) {
+            YtdlpError::Private
+        } else if has(&["members-only", "members only", "join this channel", "available to this channel's members"]) {
+            YtdlpError::MembersOnly
+        } else if has(&["confirm your age", "age-restricted", "age restricted", "inappropriate for some users"]) {
+            YtdlpError::AgeRestricted
+        } else if has(&["not a bot", "captcha", "bot check"]) {
+            YtdlpError::BotCheck
+        } else if has(&["available in your country", "blocked it in your country", "geo restrict", "geo-restrict"]) {
+            YtdlpError::GeoBlocked
+        } else if has(&["live event will begin", "premieres in", "premiere will begin", "is not live yet", "this live event will"]) {
+            YtdlpError::LiveNotStarted
+        } else if has(&["copyright claim", "copyright grounds", "due to a copyright"]) {
+            YtdlpError::CopyrightTakedown
+        } else if has(&["sign in", "login required", "cookies are no longer valid"]) {
+            YtdlpError::SignInRequired
+        } else if has(&["video unavailable", "this video is unavailable", "has been removed", "does not exist", "account associated with this video has been terminated"]) {
+            YtdlpError::VideoUnavailable
+        } else if has(&["http error 429", "too many requests"]) {
+            YtdlpError::RateLimited
+        } else if has(&["http error 403", "403: forbidden"]) {
+            YtdlpError::Forbidden
+        } else if has(&["requested format is not available", "no video formats found", "requested format not available"]) {
+            YtdlpError::FormatUnavailable
+        } else if has(&["ffmpeg", "ffprobe", "postprocessing", "conversion failed"]) {
+            YtdlpError::Ffmpeg
+        } else if has(&[
+            "unable to connect", "connection refused", "connection reset", "timed out", "network is unreachable",
+            "name or service not known", "temporary failure in name resolution", "proxyerror",
+            "tunnel connection failed", "unable to download webpage",
+        ]) {
+            YtdlpError::Network
+        } else {
+            YtdlpError::Unknown
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            YtdlpError::VideoUnavailable => "video_unavailable",
+            YtdlpError::Private => "private",
+            YtdlpError::MembersOnly => "members_only",
+            YtdlpError::AgeRestricted => "age_restricted",
+            YtdlpError::GeoBlocked => "geo_blocked",
+            YtdlpError::SignInRequired => "sign_in_required",
+            YtdlpError::BotCheck => "bot_check",
+            YtdlpError::LiveNotStarted => "live_not_started",
+            YtdlpError::CopyrightTakedown => "copyright_takedown",
+            YtdlpError::Forbidden => "forbidden",
+            YtdlpError::RateLimited => "rate_limited",
+            YtdlpError::Network => "network",
+            YtdlpError::FormatUnavailable => "format_unavailable",
+            YtdlpError::Ffmpeg => "ffmpeg_failed",
+            YtdlpError::Timeout => "timeout",
+            YtdlpError::Spawn => "ytdlp_unavailable",
+            YtdlpError::Unknown => "unknown",
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            YtdlpError::VideoUnavailable => "Video is unavailable",
+            YtdlpError::Private => "Video is private",
+            YtdlpError::MembersOnly => "Video is only available to channel members",
+            YtdlpError::AgeRestricted => "Video is age-restricted",
+            YtdlpError::GeoBlocked => "Video is not available in this region",
+            YtdlpError::SignInRequired => "YouTube requires sign-in",
+            YtdlpError::BotCheck => "YouTube bot check triggered",
+            YtdlpError::LiveNotStarted => "Live stream or premiere has not started",
+            YtdlpError::CopyrightTakedown => "Video was removed due to a copyright claim",
+            YtdlpError::Forbidden => "YouTube refused the request (HTTP 403)",
+            YtdlpError::RateLimited => "YouTube rate limit reached (HTTP 429)",
+            YtdlpError::Network => "Network error while contacting YouTube",
+            YtdlpError::FormatUnavailable => "Requested format is not available",
+            YtdlpError::Ffmpeg => "Post-processing with ffmpeg failed",
+            YtdlpError::Timeout => "yt-dlp timed out or stopped making progress",
+            YtdlpError::Spawn => "yt-dlp could not be started",
+            YtdlpError::Unknown => "yt-dlp failed",
+        }
+    }
+
+    pub fn is_permanent(&self) -> bool {
+        matches!(
+            self,
+            YtdlpError::VideoUnavailable
+                | YtdlpError::Private
+                | YtdlpError::MembersOnly
+                | YtdlpError::AgeRestricted
+                | YtdlpError::GeoBlocked
+                | YtdlpError::LiveNotStarted
+                | YtdlpError::CopyrightTakedown
+                | YtdlpError::Spawn
+        )
+    }
+
+    pub fn is_cookie_failure(&self) -> bool {
+        matches!(self, YtdlpError::SignInRequired | YtdlpError::BotCheck | YtdlpError::Forbidden)
+    }
+
+    pub fn is_proxy_failure(&self) -> bool {
+        matches!(self, YtdlpError::RateLimited | YtdlpError::Network)
+    }
+}
+
+impl fmt::Display for YtdlpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for YtdlpError {}
+
+#[derive(Clone, Debug)]
+pub struct YtdlpFailure {
+    pub error: YtdlpError,
+    pub stderr: String,
+    pub stdout: String,
+}
+
+impl YtdlpFailure {
+    pub fn from_stderr(stderr: String) -> Self {
+        Self {
+            error: YtdlpError::from_stderr(&stderr),
+            stderr,
+            stdout: String::new(),
+        }
+    }
+
+    pub fn spawn(error: std::io::Error) -> Self {
+        Self {
+            error: YtdlpError::Spawn,
+            stderr: error.to_string(),
+            stdout: String::new(),
+        }
+    }
+
+    pub fn io(error: std::io::Error) -> Self {
+        if error.kind() != std::io::ErrorKind::TimedOut {
+            return Self::spawn(error);
+        }
+
+        let message = error.to_string();
+        match error.into_inner().and_then(|inner| inner.downcast::<CommandTimeout>().ok()) {
+            Some(timeout) => {
+                let mut stderr = String::from_utf8_lossy(&timeout.stderr).to_string();
+                if !stderr.is_empty() && !stderr.ends_with('\n') {
+                    stderr.push('\n');
+                }
+                stderr.push_str(&timeout.message);
+                Self {
+                    error: YtdlpError::Timeout,
+                    stderr,
+                    stdout: String::from_utf8_lossy(&timeout.stdout).to_string(),
+                }
+            }
+            None => Self {
+                error: YtdlpError::Timeout,
+                stderr: message,
+                stdout: String::new(),
+            },
+        }
+    }
+
+    pub fn with_stdout(mut self, stdout: String) -> Self {
+        self.stdout = stdout;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_common_errors() {
+        let cases = [
+            ("ERROR: [youtube] abc: Private video. Sign in if you've been granted access", YtdlpError::Private),
+            ("ERROR: [youtube] abc: This video is private", YtdlpError::Private),
+            ("ERROR: [youtube] abc: Join this channel to get access to members-only content", YtdlpError::MembersOnly),
+            ("ERROR: [youtube] abc: Sign in to confirm your age", YtdlpError::AgeRestricted),
+            ("ERROR: [youtube] abc: Sign in to confirm you're not a bot", YtdlpError::BotCheck),
+            ("ERROR: [youtube] abc: The uploader has not made this video available in your country", YtdlpError::GeoBlocked),
+            ("ERROR: [youtube] abc: Premieres in 3 hours", YtdlpError::LiveNotStarted),
+            ("ERROR: [youtube] abc: Video unavailable", YtdlpError::VideoUnavailable),
+            ("ERROR: unable to download video data: HTTP Error 429: Too Many Requests", YtdlpError::RateLimited),
+            ("ERROR: unable to download video data: HTTP Error 403: Forbidden", YtdlpError::Forbidden),
+            ("ERROR: [youtube] abc: Requested format is not available", YtdlpError::FormatUnavailable),
+            ("ERROR: Postprocessing: ffmpeg exited with code 1", YtdlpError::Ffmpeg),
+            ("ERROR: [youtube] abc: Unable to download webpage: <urlopen error timed out>", YtdlpError::Network),
+            ("ERROR: something nobody has seen before", YtdlpError::Unknown),
+        ];
+
+        for (stderr, expected) in cases {
+            assert_eq!(YtdlpError::from_stderr(stderr), expected, "{}", stderr);
+        }
+    }
+
+    #[test]
+    fn ffmpeg_mentions_outside_error_lines_are_ignored() {
+        let stderr = "WARNING: ffmpeg not found. The downloaded format may not be the best available\n\
+                      [Merger] Merging formats with ffmpeg\n\
+                      ERROR: unable to download video data: HTTP Error 403: Forbidden";
+        assert_eq!(YtdlpError::from_stderr(stderr), YtdlpError::Forbidden);
+
+        let stderr = "[download] Destination: abc.webm\nERROR: [youtube] abc: Unable to download webpage: Connection reset by peer";
+        assert_eq!(YtdlpError::from_stderr(stderr), YtdlpError::Network);
+    }
+
+    #[test]
+    fn falls_back_to_whole_output_without_error_lines() {
+        assert_eq!(YtdlpError::from_stderr("Conversion failed!"), YtdlpError::Ffmpeg);
+        assert_eq!(YtdlpError::from_stderr(""), YtdlpError::Unknown);
+    }
+
+    #[test]
+    fn classifies_rotation_candidates() {
+        assert!(YtdlpError::Forbidden.is_cookie_failure());
+        assert!(YtdlpError::SignInRequired.is_cookie_failure());
+        assert!(!YtdlpError::Forbidden.is_permanent());
+        assert!(YtdlpError::Network.is_proxy_failure());
+        assert!(YtdlpError::Private.is_permanent());
+    }
+}
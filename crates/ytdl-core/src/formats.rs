@@ -0,0 +1,216 @@
+use serde_json::Value;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FormatInfo {
+    pub format_id: String,
+    pub has_video: bool,
+    pub has_audio: bool,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    pub fps: Option<f64>,
+    pub tbr: Option<f64>,
+    pub vbr: Option<f64>,
+    pub abr: Option<f64>,
+    pub filesize: Option<u64>,
+}
+
+impl FormatInfo {
+    fn field(&self, key: &str) -> Option<f64> {
+        match key {
+            "width" => self.width,
+            "height" => self.height,
+            "fps" => self.fps,
+            "tbr" => self.tbr,
+            "vbr" => self.vbr,
+            "abr" => self.abr,
+            "filesize" => self.filesize.map(|size| size as f64),
+            _ => None,
+        }
+    }
+
+    fn estimated_size(&self, duration: f64) -> Option<u64> {
+        self.filesize.or_else(|| {
+            let kbps = self.tbr.or_else(|| match (self.vbr, self.abr) {
+                (None, None) => None,
+                (vbr, abr) => Some(vbr.unwrap_or(0.0) + abr.unwrap_or(0.0)),
+            })?;
+            (kbps > 0.0 && duration > 0.0).then_some((kbps * duration * 125.0) as u64)
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoInfo {
+    pub duration: f64,
+    pub formats: Vec<FormatInfo>,
+}
+
+fn codec_present(value: &Value) -> bool {
+    value.as_str().is_some_and(|codec| codec != "none")
+}
+
+pub fn parse_video_info(json: &Value) -> Option<VideoInfo> {
+    let duration = json["duration"].as_f64()?;
+    let formats = json["formats"].as_array().into_iter().flatten()
+        .map(|format| FormatInfo {
+            format_id: format["format_id"].as_str().unwrap_or_default().to_string(),
+            has_video: codec_present(&format["vcodec"]),
+            has_audio: codec_present(&format["acodec"]),
+            width: format["width"].as_f64(),
+            height: format["height"].as_f64(),
+            fps: format["fps"].as_f64(),
+            tbr: format["tbr"].as_f64(),
+            vbr: format["vbr"].as_f64(),
+            abr: format["abr"].as_f64(),
+            filesize: format["filesize"].as_u64().or_else(|| format["filesize_approx"].as_u64()),
+        })
+        .collect();
+
+    Some(VideoInfo { duration, formats })
+}
+
+fn matches_filter(format: &FormatInfo, filter: &str) -> bool {
+    let operators = ["<=", ">=", "!=", "=", "<", ">"];
+    let Some((key, op, value)) = operators.iter().find_map(|op| {
+        filter.split_once(op).map(|(key, value)| (key.trim(), *op, value.trim()))
+    }) else {
+        return false;
+    };
+
+    let (Some(actual), Ok(expected)) = (format.field(key), value.parse::<f64>()) else {
+        return false;
+    };
+
+    match op {
+        "<=" => actual <= expected,
+        ">=" => actual >= expected,
+        "!=" => actual != expected,
+        "=" => actual == expected,
+        "<" => actual < expected,
+        _ => actual > expected,
+    }
+}
+
+fn select_format<'a>(spec: &str, formats: &'a [FormatInfo]) -> Option<&'a FormatInfo> {
+    let (kind, filters) = match spec.find('[') {
+        Some(index) => (&spec[..index], &spec[index..]),
+        None => (spec, ""),
+    };
+    let filters: Vec<&str> = filters.split(['[', ']']).filter(|f| !f.is_empty()).collect();
+
+    formats.iter().rfind(|format| {
+        let kind_matches = match kind {
+            "bestvideo" => format.has_video && !format.has_audio,
+            "bestaudio" => format.has_audio && !format.has_video,
+            "best" => format.has_video && format.has_audio,
+            _ => false,
+        };
+        kind_matches && filters.iter().all(|filter| matches_filter(format, filter))
+    })
+}
+
+pub fn resolve_formats<'a>(selector: &str, info: &'a VideoInfo) -> Option<Vec<&'a FormatInfo>> {
+    selector.split('/').find_map(|alternative| {
+        alternative.split('+')
+            .map(|spec| select_format(spec.trim(), &info.formats))
+            .collect::<Option<Vec<_>>>()
+    })
+}
+
+pub fn estimate_size(selector: &str, info: &VideoInfo) -> Option<u64> {
+    resolve_formats(selector, info)?
+        .into_iter()
+        .map(|format| format.estimated_size(info.duration))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample_info() -> VideoInfo {
+        parse_video_info(&json!({
+            "duration": 100.0,
+            "formats": [
+                {"format_id": "249", "vcodec": "none", "acodec": "opus", "abr": 50.0, "filesize": 1000},
+                {"format_id": "140", "vcodec": "none", "acodec": "mp4a.40.2", "abr": 128.0, "tbr": 129.5},
+                {"format_id": "18", "vcodec": "avc1.42001E", "acodec": "mp4a.40.2", "height": 360, "fps": 30, "filesize_approx": 5000},
+                {"format_id": "136", "vcodec": "avc1.4d401f", "acodec": "none", "height": 720, "fps": 30, "vbr": 1000.0},
+                {"format_id": "298", "vcodec": "avc1.4d4020", "acodec": "none", "height": 720, "fps": 60, "filesize": 90000},
+                {"format_id": "399", "vcodec": "av01.0.08M.08", "acodec": "none", "tbr": 2000.0},
+            ],
+        }))
+        .unwrap()
+    }
+
+    fn ids(selector: &str, info: &VideoInfo) -> Option<Vec<String>> {
+        resolve_formats(selector, info).map(|formats| formats.iter().map(|f| f.format_id.clone()).collect())
+    }
+
+    #[test]
+    fn height_filter_picks_the_best_match_below_the_limit() {
+        let info = sample_info();
+        assert_eq!(ids("bestvideo[height<=720]", &info), Some(vec!["298".to_string()]));
+        assert_eq!(ids("bestvideo[height<=480]", &info), None);
+        assert_eq!(ids("best[height<=720]", &info), Some(vec!["18".to_string()]));
+    }
+
+    #[test]
+    fn fps_filter_is_combined_with_height() {
+        let info = sample_info();
+        assert_eq!(ids("bestvideo[height=720][fps=60]", &info), Some(vec!["298".to_string()]));
+        assert_eq!(ids("bestvideo[height=720][fps<60]", &info), Some(vec!["136".to_string()]));
+        assert_eq!(ids("bestvideo[height=1080][fps=60]", &info), None);
+    }
+
+    #[test]
+    fn falls_back_to_the_next_alternative() {
+        let info = sample_info();
+        assert_eq!(ids("bestvideo+bestaudio/best", &info), Some(vec!["399".to_string(), "140".to_string()]));
+        assert_eq!(ids("bestvideo[height=2160]+bestaudio/best", &info), Some(vec!["18".to_string()]));
+        assert_eq!(ids("bestvideo[height=2160]+bestaudio/best[height=2160]", &info), None);
+    }
+
+    #[test]
+    fn missing_fields_never_match_filters() {
+        let info = sample_info();
+        let av1 = &info.formats[5];
+        assert!(av1.has_video && !av1.has_audio);
+        assert_eq!((av1.height, av1.fps, av1.filesize), (None, None, None));
+        assert!(!matches_filter(av1, "height<=720"));
+        assert!(!matches_filter(av1, "height!=720"));
+        assert!(!matches_filter(&info.formats[3], "codec=1"));
+        assert!(!matches_filter(&info.formats[3], "height<=abc"));
+
+        let info = parse_video_info(&json!({"duration": 10.0, "formats": [{"format_id": "x"}]})).unwrap();
+        assert!(!info.formats[0].has_video && !info.formats[0].has_audio);
+        assert_eq!(parse_video_info(&json!({"formats": []})), None);
+        assert_eq!(parse_video_info(&json!({"duration": 10.0})).map(|info| info.formats.len()), Some(0));
+    }
+
+    #[test]
+    fn estimates_size_from_filesize_or_bitrate() {
+        let info = sample_info();
+        assert_eq!(estimate_size("bestaudio[abr<=64]", &info), Some(1000));
+        assert_eq!(estimate_size("best", &info), Some(5000));
+        // tbr 129.5 kbit/s over 100s
+        assert_eq!(estimate_size("bestaudio", &info), Some(1_618_750));
+        // vbr only: 1000 kbit/s over 100s
+        assert_eq!(estimate_size("bestvideo[fps<60]", &info), Some(12_500_000));
+        assert_eq!(estimate_size("bestvideo[height=720][fps=60]+bestaudio", &info), Some(90_000 + 1_618_750));
+        assert_eq!(estimate_size("bestvideo[height=2160]", &info), None);
+    }
+
+    #[test]
+    fn unknown_bitrate_gives_no_estimate() {
+        let info = parse_video_info(&json!({
+            "duration": 100.0,
+            "formats": [{"format_id": "1", "vcodec": "vp9", "acodec": "none"}, {"format_id": "2", "vcodec": "none", "acodec": "opus", "abr": 0.0}],
+        }))
+        .unwrap();
+        assert_eq!(estimate_size("bestvideo", &info), None);
+        assert_eq!(estimate_size("bestaudio", &info), None);
+        assert_eq!(estimate_size("bestvideo+bestaudio", &info), None);
+    }
+}
@@ -0,0 +1,17 @@
+pub mod disk;
+pub mod downloader;
+pub mod errors;
+pub mod formats;
+pub mod probe;
+pub mod process;
+pub mod redact;
+pub mod transcode;
+pub mod youtube;
+
+pub use disk::{device_id, disk_space, DiskSpace};
+pub use downloader::*;
+pub use errors::{YtdlpError, YtdlpFailure};
+pub use formats::{estimate_size, FormatInfo, VideoInfo};
+pub use probe::MediaInfo;
+pub use redact::redact_output;
+pub use youtube::extract_video_id;
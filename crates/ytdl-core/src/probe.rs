@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use crate::downloader::MediaType;
+use crate::process::{run_command, CommandLimits};
+use crate::youtube::stderr_excerpt;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MediaInfo {
+    pub container: String,
+    pub duration: f64,
+    pub bitrate: Option<u64>,
+    pub video_codec: Option<String>,
+    pub width: Option<u64>,
+    pub height: Option<u64>,
+    pub audio_codec: Option<String>,
+    pub audio_bitrate: Option<u64>,
+    pub sample_rate: Option<u64>,
+    pub channels: Option<u64>,
+}
+
+fn number(value: &Value) -> Option<u64> {
+    value.as_u64().or_else(|| value.as_str().and_then(|s| s.parse().ok()))
+}
+
+fn parse_media_info(json: &Value) -> MediaInfo {
+    let format = &json["format"];
+    let mut info = MediaInfo {
+        container: format["format_name"].as_str().unwrap_or_default().to_string(),
+        duration: format["duration"].as_str().and_then(|s| s.parse().ok()).unwrap_or(0.0),
+        bitrate: number(&format["bit_rate"]),
+        ..Default::default()
+    };
+
+    for stream in json["streams"].as_array().into_iter().flatten() {
+        let codec = stream["codec_name"].as_str().map(String::from);
+        match stream["codec_type"].as_str() {
+            Some("video") if stream["disposition"]["attached_pic"].as_u64() != Some(1) && info.video_codec.is_none() => {
+                info.video_codec = codec;
+                info.width = number(&stream["width"]);
+                info.height = number(&stream["height"]);
+            }
+            Some("audio") if info.audio_codec.is_none() => {
+                info.audio_codec = codec;
+                info.audio_bitrate = number(&stream["bit_rate"]);
+                info.sample_rate = number(&stream["sample_rate"]);
+                info.channels = number(&stream["channels"]);
+            }
+            _ => {}
+        }
+    }
+
+    info
+}
+
+pub async fn probe_media(ffprobe: &str, path: &Path, limits: &CommandLimits) -> Result<Option<MediaInfo>, String> {
+    let args = vec![
+        "-v".to_string(), "error".to_string(),
+        "-print_format".to_string(), "json".to_string(),
+        "-show_format".to_string(),
+        "-show_streams".to_string(),
+        path.to_string_lossy().to_string(),
+    ];
+
+    let output = match run_command(ffprobe, &args, &limits.without_stall_timeout()).await {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Err(e.to_string()),
+        Err(e) => {
+            tracing::warn!(error = %e, "ffprobe could not be started; skipping output validation");
+            return Ok(None);
+        }
+    };
+
+    if !output.status.success() {
+        return Err(stderr_excerpt(&String::from_utf8_lossy(&output.stderr), 300));
+    }
+
+    let json: Value = serde_json::from_slice(&output.stdout).map_err(|e| format!("unreadable ffprobe output: {}", e))?;
+    Ok(Some(parse_media_info(&json)))
+}
+
+pub fn validate_media(media_type: MediaType, info: &MediaInfo, expected_duration: Option<f64>, tolerance: f64) -> Result<(), String> {
+    let container = match media_type {
+        MediaType::Audio => "mp3",
+        MediaType::Video | MediaType::Merge => "mp4",
+    };
+    if !info.container.split(',').any(|name| name == container) {
+        return Err(format!("expected {} container, found {:?}", container, info.container));
+    }
+
+    match media_type {
+        MediaType::Audio if info.audio_codec.as_deref() != Some("mp3") => {
+            return Err(format!("expected mp3 audio stream, found {:?}", info.audio_codec));
+        }
+        MediaType::Video | MediaType::Merge if info.video_codec.is_none() => {
+            return Err("missing video stream".to_string());
+        }
+        MediaType::Merge if info.audio_codec.is_none() => {
+            return Err("missing audio stream".to_string());
+        }
+        _ => {}
+    }
+
+    if info.duration <= 0.0 {
+        return Err("empty or unknown duration".to_string());
+    }
+    if let Some(expected) = expected_duration.filter(|d| *d > 0.0) {
+        if (info.duration - expected).abs() > tolerance {
+            return Err(format!("duration {:.1}s does not match expected {:.1}s", info.duration, expected));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn mp4(duration: f64) -> MediaInfo {
+        MediaInfo {
+            container: "mov,mp4,m4a,3gp,3g2,mj2".to_string(),
+            duration,
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn mp3(duration: f64) -> MediaInfo {
+        MediaInfo {
+            container: "mp3".to_string(),
+            duration,
+            audio_codec: Some("mp3".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_matching_outputs() {
+        assert_eq!(validate_media(MediaType::Merge, &mp4(100.0), Some(101.5), 2.0), Ok(()));
+        assert_eq!(validate_media(MediaType::Audio, &mp3(100.0), Some(100.0), 0.0), Ok(()));
+        let video_only = MediaInfo { audio_codec: None, ..mp4(100.0) };
+        assert_eq!(validate_media(MediaType::Video, &video_only, None, 2.0), Ok(()));
+    }
+
+    #[test]
+    fn rejects_the_wrong_container() {
+        let webm = MediaInfo { container: "matroska,webm".to_string(), ..mp4(100.0) };
+        assert!(validate_media(MediaType::Merge, &webm, None, 2.0).unwrap_err().starts_with("expected mp4 container"));
+        assert!(validate_media(MediaType::Audio, &mp4(100.0), None, 2.0).unwrap_err().starts_with("expected mp3 container"));
+        // A container name must match a whole entry, not a substring.
+        let m4a = MediaInfo { container: "m4a".to_string(), ..mp4(100.0) };
+        assert!(validate_media(MediaType::Video, &m4a, None, 2.0).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_or_wrong_codecs() {
+        let opus = MediaInfo { audio_codec: Some("opus".to_string()), ..mp3(100.0) };
+        assert!(validate_media(MediaType::Audio, &opus, None, 2.0).unwrap_err().starts_with("expected mp3 audio stream"));
+        let silent = MediaInfo { audio_codec: None, ..mp4(100.0) };
+        assert_eq!(validate_media(MediaType::Merge, &silent, None, 2.0), Err("missing audio stream".to_string()));
+        let audio_only = MediaInfo { video_codec: None, ..mp4(100.0) };
+        assert_eq!(validate_media(MediaType::Video, &audio_only, None, 2.0), Err("missing video stream".to_string()));
+        assert_eq!(validate_media(MediaType::Merge, &audio_only, None, 2.0), Err("missing video stream".to_string()));
+    }
+
+    #[test]
+    fn checks_duration_against_the_tolerance() {
+        assert_eq!(validate_media(MediaType::Merge, &mp4(102.0), Some(100.0), 2.0), Ok(()));
+        assert_eq!(
+            validate_media(MediaType::Merge, &mp4(102.1), Some(100.0), 2.0),
+            Err("duration 102.1s does not match expected 100.0s".to_string())
+        );
+        assert!(validate_media(MediaType::Audio, &mp3(50.0), Some(100.0), 2.0).is_err());
+    }
+
+    #[test]
+    fn duration_edge_cases() {
+        assert_eq!(validate_media(MediaType::Merge, &mp4(0.0), None, 2.0), Err("empty or unknown duration".to_string()));
+        assert!(validate_media(MediaType::Merge, &mp4(-1.0), Some(100.0), 2.0).is_err());
+        // Unknown or zero expected durations (live streams, failed probes) skip the comparison.
+        assert_eq!(validate_media(MediaType::Merge, &mp4(100.0), None, 0.0), Ok(()));
+        assert_eq!(validate_media(MediaType::Merge, &mp4(100.0), Some(0.0), 0.0), Ok(()));
+    }
+
+    #[test]
+    fn parses_ffprobe_output_and_skips_cover_art() {
+        let info = parse_media_info(&json!({
+            "format": {"format_name": "mp3", "duration": "212.4", "bit_rate": "320000"},
+            "streams": [
+                {"codec_type": "audio", "codec_name": "mp3", "bit_rate": "320000", "sample_rate": "44100", "channels": 2},
+                {"codec_type": "video", "codec_name": "mjpeg", "width": 1280, "height": 720, "disposition": {"attached_pic": 1}},
+            ],
+        }));
+
+        assert_eq!(info.container, "mp3");
+        assert_eq!(info.duration, 212.4);
+        assert_eq!(info.bitrate, Some(320000));
+        assert_eq!(info.audio_codec.as_deref(), Some("mp3"));
+        assert_eq!((info.sample_rate, info.channels), (Some(44100), Some(2)));
+        assert_eq!((info.video_codec, info.width), (None, None));
+
+        let empty = parse_media_info(&json!({}));
+        assert_eq!(empty, MediaInfo::default());
+        assert!(validate_media(MediaType::Audio, &empty, None, 2.0).is_err());
+    }
+}
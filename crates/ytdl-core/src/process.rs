@@ -0,0 +1,272 @@
+use dashmap::DashMap;
+use std::fmt;
+use std::io::{Error, ErrorKind};
+use std::process::{Output, Stdio};
+use std::sync::OnceLock;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::{Child, Command};
+use tokio::time::{sleep, sleep_until, Duration, Instant};
+
+static CHILDREN: OnceLock<DashMap<u32, String>> = OnceLock::new();
+
+const MAX_CAPTURED_OUTPUT: usize = 8 * 1024 * 1024;
+
+fn children() -> &'static DashMap<u32, String> {
+    CHILDREN.get_or_init(DashMap::new)
+}
+
+struct ChildGuard(Option<u32>);
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(pid) = self.0 {
+            children().remove(&pid);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CommandTimeout {
+    pub message: String,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl fmt::Display for CommandTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CommandTimeout {}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CommandLimits {
+    pub timeout: Option<Duration>,
+    pub stall_timeout: Option<Duration>,
+    pub cpu_seconds: Option<u64>,
+    pub memory_bytes: Option<u64>,
+    pub open_files: Option<u64>,
+}
+
+impl CommandLimits {
+    /// The same limits for commands that print nothing until they finish,
+    /// where only the wall-clock timeout is meaningful.
+    pub fn without_stall_timeout(&self) -> Self {
+        Self { stall_timeout: None, ..*self }
+    }
+}
+
+#[cfg(unix)]
+fn apply_resource_limits(command: &mut Command, limits: &CommandLimits) {
+    let CommandLimits { cpu_seconds, memory_bytes, open_files, .. } = *limits;
+    if cpu_seconds.is_none() && memory_bytes.is_none() && open_files.is_none() {
+        return;
+    }
+
+    // Runs in the forked child before exec, so it only touches the stack and
+    // reports failures through errno, which makes the spawn itself fail.
+    let set_limit = |resource, value: u64| -> std::io::Result<()> {
+        let rlimit = libc::rlimit { rlim_cur: value as libc::rlim_t, rlim_max: value as libc::rlim_t };
+        // SAFETY: `rlimit` is a valid, initialised struct that outlives the call.
+        if unsafe { libc::setrlimit(resource, &rlimit) } != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(())
+    };
+
+    // SAFETY: the closure is async-signal-safe. It copies plain integers,
+    // calls only setrlimit(2) and builds errors from errno without allocating,
+    // so it cannot deadlock on a lock held by another thread of this process.
+    unsafe {
+        command.pre_exec(move || {
+            if let Some(seconds) = cpu_seconds {
+                set_limit(libc::RLIMIT_CPU, seconds)?;
+            }
+            if let Some(bytes) = memory_bytes {
+                set_limit(libc::RLIMIT_AS, bytes)?;
+            }
+            if let Some(files) = open_files {
+                set_limit(libc::RLIMIT_NOFILE, files)?;
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) {
+    match child.id() {
+        // SAFETY: kill(2) takes plain integers and touches no memory. The child
+        // was spawned as the leader of its own process group, so the negated
+        // pid addresses that group and cannot reach this server's processes.
+        Some(pid) => unsafe {
+            libc::kill(-(pid as i32), libc::SIGKILL);
+        },
+        None => {
+            let _ = child.start_kill();
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) {
+    let _ = child.start_kill();
+}
+
+async fn read_chunk<R: AsyncRead + Unpin>(pipe: &mut Option<R>, buf: &mut [u8]) -> std::io::Result<usize> {
+    match pipe {
+        Some(reader) => reader.read(buf).await,
+        None => std::future::pending().await,
+    }
+}
+
+async fn wait_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+fn append_capped(buffer: &mut Vec<u8>, chunk: &[u8]) {
+    buffer.extend_from_slice(chunk);
+    if buffer.len() > 2 * MAX_CAPTURED_OUTPUT {
+        let excess = buffer.len() - MAX_CAPTURED_OUTPUT;
+        buffer.drain(..excess);
+    }
+}
+
+async fn kill_timed_out(child: &mut Child, program: &str, reason: String, stdout: Vec<u8>, stderr: Vec<u8>) -> Error {
+    kill_process_group(child);
+    let _ = child.wait().await;
+    tracing::warn!(program, reason = %reason, "killed child process group");
+    Error::new(ErrorKind::TimedOut, CommandTimeout {
+        message: format!("{} {}", program, reason),
+        stdout,
+        stderr,
+    })
+}
+
+pub async fn run_command(program: &str, args: &[String], limits: &CommandLimits) -> std::io::Result<Output> {
+    run_command_streaming(program, args, limits, |_| {}).await
+}
+
+/// Like [`run_command`], but also hands each stdout chunk to `on_stdout` as it arrives.
+pub async fn run_command_streaming<F>(program: &str, args: &[String], limits: &CommandLimits, mut on_stdout: F) -> std::io::Result<Output>
+where
+    F: FnMut(&[u8]),
+{
+    let mut command = Command::new(program);
+    command
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    #[cfg(unix)]
+    {
+        command.process_group(0);
+        apply_resource_limits(&mut command, limits);
+    }
+
+    let mut child = command.spawn().map_err(|e| {
+        tracing::warn!(program, error = %e, ?limits, "could not start child process");
+        Error::new(e.kind(), format!("{} could not be started: {}", program, e))
+    })?;
+    let _guard = ChildGuard(child.id());
+    if let Some(pid) = child.id() {
+        children().insert(pid, program.to_string());
+    }
+
+    let mut stdout_pipe = child.stdout.take();
+    let mut stderr_pipe = child.stderr.take();
+    let (mut stdout, mut stderr) = (Vec::new(), Vec::new());
+    let (mut stdout_buf, mut stderr_buf) = ([0u8; 8192], [0u8; 8192]);
+
+    let started = Instant::now();
+    let wall_deadline = limits.timeout.map(|timeout| started + timeout);
+    let mut last_activity = started;
+
+    while stdout_pipe.is_some() || stderr_pipe.is_some() {
+        let stall_deadline = limits.stall_timeout.map(|stall| last_activity + stall);
+        tokio::select! {
+            read = read_chunk(&mut stdout_pipe, &mut stdout_buf) => match read {
+                Ok(0) | Err(_) => stdout_pipe = None,
+                Ok(n) => {
+                    on_stdout(&stdout_buf[..n]);
+                    append_capped(&mut stdout, &stdout_buf[..n]);
+                    last_activity = Instant::now();
+                }
+            },
+            read = read_chunk(&mut stderr_pipe, &mut stderr_buf) => match read {
+                Ok(0) | Err(_) => stderr_pipe = None,
+                Ok(n) => {
+                    append_capped(&mut stderr, &stderr_buf[..n]);
+                    last_activity = Instant::now();
+                }
+            },
+            _ = wait_until(wall_deadline) => {
+                let timeout = limits.timeout.unwrap_or_default().as_secs();
+                let reason = format!("exceeded the {}s time limit", timeout);
+                return Err(kill_timed_out(&mut child, program, reason, stdout, stderr).await);
+            }
+            _ = wait_until(stall_deadline) => {
+                let stall = limits.stall_timeout.unwrap_or_default().as_secs();
+                let reason = format!("stalled with no output for {}s", stall);
+                return Err(kill_timed_out(&mut child, program, reason, stdout, stderr).await);
+            }
+        }
+    }
+
+    let status = tokio::select! {
+        status = child.wait() => status?,
+        _ = wait_until(wall_deadline) => {
+            let timeout = limits.timeout.unwrap_or_default().as_secs();
+            let reason = format!("exceeded the {}s time limit", timeout);
+            return Err(kill_timed_out(&mut child, program, reason, stdout, stderr).await);
+        }
+    };
+
+    Ok(Output { status, stdout, stderr })
+}
+
+pub fn running_children() -> usize {
+    children().len()
+}
+
+#[cfg(unix)]
+fn signal_children(signal: i32) {
+    for entry in children().iter() {
+        let pgid = *entry.key() as i32;
+        // SAFETY: kill(2) takes plain integers and touches no memory. Every
+        // registered child leads its own process group, so -pgid only signals
+        // that child and its descendants.
+        unsafe {
+            libc::kill(-pgid, signal);
+        }
+    }
+}
+
+#[cfg(unix)]
+pub async fn terminate_children(grace: Duration) {
+    if children().is_empty() {
+        return;
+    }
+
+    tracing::warn!(count = running_children(), "sending SIGTERM to running child processes");
+    signal_children(libc::SIGTERM);
+
+    let deadline = Instant::now() + grace;
+    while !children().is_empty() && Instant::now() < deadline {
+        sleep(Duration::from_millis(200)).await;
+    }
+
+    if !children().is_empty() {
+        tracing::warn!(count = running_children(), "sending SIGKILL to child processes that ignored SIGTERM");
+        signal_children(libc::SIGKILL);
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn terminate_children(_grace: Duration) {}
@@ -0,0 +1,78 @@
+use std::path::Path;
+use crate::process::{run_command, CommandLimits};
+use crate::youtube::stderr_excerpt;
+
+fn ffmpeg_program(ffmpeg: &str) -> &str {
+    if ffmpeg.is_empty() { "ffmpeg" } else { ffmpeg }
+}
+
+async fn run_ffmpeg(ffmpeg: &str, args: Vec<String>, limits: &CommandLimits) -> Result<(), String> {
+    let mut full_args = vec![
+        "-y".to_string(),
+        "-nostdin".to_string(),
+        "-loglevel".to_string(), "error".to_string(),
+        "-nostats".to_string(),
+        "-progress".to_string(), "pipe:1".to_string(),
+    ];
+    full_args.extend(args);
+
+    let output = run_command(ffmpeg_program(ffmpeg), &full_args, limits).await.map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(stderr_excerpt(&String::from_utf8_lossy(&output.stderr), 500))
+    }
+}
+
+pub async fn extract_audio(ffmpeg: &str, input: &Path, output: &Path, limits: &CommandLimits) -> Result<(), String> {
+    let input = input.to_string_lossy().to_string();
+    let output = output.to_string_lossy().to_string();
+
+    // Merge downloads carry the yt-dlp thumbnail as an attached picture after
+    // the real video stream, so the cover matches a direct audio download.
+    // Older renditions without one simply produce an mp3 without cover art.
+    let with_cover = vec![
+        "-i".to_string(), input.clone(),
+        "-map".to_string(), "0:a:0".to_string(),
+        "-map".to_string(), "0:v:1?".to_string(),
+        "-frames:v".to_string(), "1".to_string(),
+        "-c:a".to_string(), "libmp3lame".to_string(),
+        "-q:a".to_string(), "0".to_string(),
+        "-c:v".to_string(), "mjpeg".to_string(),
+        "-disposition:v".to_string(), "attached_pic".to_string(),
+        "-map_metadata".to_string(), "0".to_string(),
+        "-id3v2_version".to_string(), "3".to_string(),
+        output.clone(),
+    ];
+
+    match run_ffmpeg(ffmpeg, with_cover, limits).await {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            tracing::debug!(error = %e, "audio extraction with cover art failed; retrying without it");
+            let audio_only = vec![
+                "-i".to_string(), input,
+                "-map".to_string(), "0:a:0".to_string(),
+                "-vn".to_string(),
+                "-c:a".to_string(), "libmp3lame".to_string(),
+                "-q:a".to_string(), "0".to_string(),
+                "-map_metadata".to_string(), "0".to_string(),
+                "-id3v2_version".to_string(), "3".to_string(),
+                output,
+            ];
+            run_ffmpeg(ffmpeg, audio_only, limits).await
+        }
+    }
+}
+
+pub async fn strip_audio(ffmpeg: &str, input: &Path, output: &Path, limits: &CommandLimits) -> Result<(), String> {
+    let args = vec![
+        "-i".to_string(), input.to_string_lossy().to_string(),
+        "-map".to_string(), "0:v:0".to_string(),
+        "-an".to_string(),
+        "-c:v".to_string(), "copy".to_string(),
+        "-map_metadata".to_string(), "0".to_string(),
+        "-movflags".to_string(), "+faststart".to_string(),
+        output.to_string_lossy().to_string(),
+    ];
+    run_ffmpeg(ffmpeg, args, limits).await
+}
@@ -0,0 +1,242 @@
+use regex::Regex;
+use std::path::Path;
+use std::process::Output;
+use crate::process::{run_command, run_command_streaming, CommandLimits};
+use crate::errors::YtdlpFailure;
+use crate::formats::{parse_video_info, VideoInfo};
+use crate::redact::redact_output;
+
+pub fn extract_video_id(url: &str) -> Result<String, String> {
+    let re = Regex::new(r"(?:youtube\.com\/(?:watch\?v=|shorts\/|embed\/|v\/)|youtu\.be\/|music\.youtube\.com\/watch\?v=|googleusercontent\.com\/youtube\.com\/[0-2])([a-zA-Z0-9_-]{10,12})").unwrap();
+
+    if let Some(caps) = re.captures(url) {
+        Ok(caps[1].to_string())
+    } else if url.len() == 11 && !url.contains('/') && !url.contains('.') {
+        Ok(url.to_string())
+    } else {
+        Err("Invalid YouTube URL or ID".to_string())
+    }
+}
+
+pub async fn get_video_info(url: &str, cookies: Option<&str>, proxy: Option<&str>, limits: &CommandLimits) -> Result<Option<VideoInfo>, YtdlpFailure> {
+    let mut args = vec![
+        "--dump-json".to_string(),
+        "--no-playlist".to_string(),
+        "--remote-components".to_string(),
+        "ejs:github".to_string(),
+        url.to_string(),
+    ];
+
+    if let Some(cookie_file) = cookies {
+        args.insert(0, "--cookies".to_string());
+        args.insert(1, cookie_file.to_string());
+    }
+
+    if let Some(proxy) = proxy {
+        args.insert(0, "--proxy".to_string());
+        args.insert(1, proxy.to_string());
+    }
+
+    // --dump-json stays silent until the whole probe is done.
+    let output = run_command("yt-dlp", &args, &limits.without_stall_timeout()).await.map_err(YtdlpFailure::io)?;
+    if !output.status.success() {
+        let failure = YtdlpFailure::from_stderr(String::from_utf8_lossy(&output.stderr).to_string());
+        tracing::warn!(
+            exit_code = output.status.code().unwrap_or(-1),
+            error_code = failure.error.code(),
+            stderr = %log_excerpt(&failure.stderr, cookies, 1000),
+            "metadata probe failed"
+        );
+        return Err(failure);
+    }
+
+    let json: serde_json::Value = match serde_json::from_slice(&output.stdout) {
+        Ok(json) => json,
+        Err(_) => return Ok(None),
+    };
+
+    Ok(parse_video_info(&json))
+}
+
+/// Percentage from a yt-dlp `[download]  42.0% of ...` progress line.
+pub fn parse_download_percent(line: &str) -> Option<f64> {
+    let rest = line.trim().strip_prefix("[download]")?;
+    let (number, _) = rest.split_once('%')?;
+    number.trim().parse().ok()
+}
+
+/// Redacted excerpt of yt-dlp output for the server log.
+fn log_excerpt(output: &str, cookies: Option<&str>, max_len: usize) -> String {
+    let cookies_dir = cookies.and_then(|c| Path::new(c).parent()).map(|dir| dir.to_string_lossy()).unwrap_or_default();
+    stderr_excerpt(&redact_output(output, &cookies_dir), max_len)
+}
+
+pub fn stderr_excerpt(stderr: &str, max_len: usize) -> String {
+    let trimmed = stderr.trim();
+    if trimmed.len() <= max_len {
+        return trimmed.to_string();
+    }
+
+    let mut start = trimmed.len() - max_len;
+    while !trimmed.is_char_boundary(start) {
+        start += 1;
+    }
+    format!("...{}", &trimmed[start..])
+}
+
+pub fn get_format_hierarchy(media_type: &str) -> Vec<(&'static str, &'static str, &'static str)> {
+    match media_type {
+        "audio" => vec![
+            ("bestaudio[abr<=128]", ".mp3", "audio"),
+            ("bestaudio[abr<=192]", ".mp3", "audio"),
+            ("bestaudio", ".mp3", "audio"),
+        ],
+        "video" => vec![
+            ("bestvideo[height=720][fps=60]", ".mp4", "video"),
+            ("bestvideo[height=720]", ".mp4", "video"),
+            ("bestvideo[height=480]", ".mp4", "video"),
+            ("bestvideo[height=360]", ".mp4", "video"),
+            ("bestvideo[height=1080]", ".mp4", "video"),
+            ("bestvideo[height=1440]", ".mp4", "video"),
+            ("bestvideo[height=2160]", ".mp4", "video"),
+            ("bestvideo", ".mp4", "video"),
+        ],
+        "merge" => vec![
+            ("bestvideo[height=720][fps=60]+bestaudio[abr<=192]/best[height=720][fps=60]", ".mp4", "video"),
+            ("bestvideo[height=720]+bestaudio[abr<=192]/best[height=720]", ".mp4", "video"),
+            ("bestvideo[height=480]+bestaudio[abr<=128]/best[height=480]", ".mp4", "video"),
+            ("bestvideo[height=360]+bestaudio/best[height=360]", ".mp4", "video"),
+            ("bestvideo[height=1080]+bestaudio/best[height=1080]", ".mp4", "video"),
+            ("bestvideo[height=1440]+bestaudio/best[height=1440]", ".mp4", "video"),
+            ("bestvideo[height=2160]+bestaudio/best[height=2160]", ".mp4", "video"),
+            ("bestvideo+bestaudio/best", ".mp4", "video"),
+        ],
+        _ => vec![],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn execute_ytdlp(
+    url: &str,
+    format: &str,
+    output: &str,
+    post_proc: &str,
+    max_concurrent: usize,
+    ffmpeg: &str,
+    cookies: Option<&str>,
+    proxy: Option<&str>,
+    limits: &CommandLimits,
+    mut on_percent: impl FnMut(f64),
+) -> Result<Output, YtdlpFailure> {
+    let mut args = vec![
+        "-f".to_string(), format.to_string(),
+        "-o".to_string(), output.to_string(),
+        "--no-playlist".to_string(),
+        "--no-warnings".to_string(),
+        "--newline".to_string(),
+        "--remote-components".to_string(), "ejs:github".to_string(),
+        "--concurrent-fragments".to_string(), max_concurrent.to_string(),
+        "--buffer-size".to_string(), "1M".to_string(),
+        "--http-chunk-size".to_string(), "10M".to_string(),
+        "--throttled-rate".to_string(), "100K".to_string(),
+        "--retries".to_string(), "10".to_string(),
+        "--fragment-retries".to_string(), "10".to_string(),
+        "--file-access-retries".to_string(), "10".to_string(),
+        "--no-part".to_string(),
+        "--no-mtime".to_string(),
+        "--continue".to_string(),
+    ];
+
+    if let Some(cookie_file) = cookies {
+        args.push("--cookies".to_string());
+        args.push(cookie_file.to_string());
+    }
+
+    if let Some(proxy) = proxy {
+        args.push("--proxy".to_string());
+        args.push(proxy.to_string());
+    }
+
+    if !ffmpeg.is_empty() {
+        args.push("--ffmpeg-location".to_string());
+        args.push(ffmpeg.to_string());
+    }
+
+    if post_proc == "audio" {
+        args.extend(vec![
+            "-x".to_string(),
+            "--audio-format".to_string(), "mp3".to_string(),
+            "--audio-quality".to_string(), "0".to_string(),
+            "--embed-metadata".to_string(),
+            "--embed-thumbnail".to_string(),
+            "--convert-thumbnails".to_string(), "jpg".to_string(),
+        ]);
+    } else if post_proc == "video" {
+        args.extend(vec![
+            "--merge-output-format".to_string(), "mp4".to_string(),
+            "--remux-video".to_string(), "mp4".to_string(),
+            "--embed-metadata".to_string(),
+            "--embed-thumbnail".to_string(),
+            "--convert-thumbnails".to_string(), "jpg".to_string(),
+        ]);
+    }
+
+    args.push(url.to_string());
+
+    let mut pending = Vec::new();
+    let on_stdout = |chunk: &[u8]| {
+        pending.extend_from_slice(chunk);
+        while let Some(end) = pending.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            if let Some(percent) = parse_download_percent(&String::from_utf8_lossy(&line)) {
+                on_percent(percent);
+            }
+        }
+    };
+
+    let output = run_command_streaming("yt-dlp", &args, limits, on_stdout)
+        .await
+        .map_err(|e| {
+            let failure = YtdlpFailure::io(e);
+            tracing::error!(format, error_code = failure.error.code(), error = %log_excerpt(&failure.stderr, cookies, 1000), "yt-dlp did not finish");
+            failure
+        })?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+    let exit_code = output.status.code().unwrap_or(-1);
+
+    if !output.status.success() {
+        let failure = YtdlpFailure::from_stderr(stderr).with_stdout(String::from_utf8_lossy(&output.stdout).to_string());
+        tracing::warn!(
+            format,
+            exit_code,
+            error_code = failure.error.code(),
+            stderr = %log_excerpt(&failure.stderr, cookies, 1000),
+            "format attempt failed"
+        );
+        return Err(failure);
+    }
+
+    tracing::info!(format, exit_code, stderr = %log_excerpt(&stderr, cookies, 300), "format attempt succeeded");
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_download_progress_lines() {
+        assert_eq!(parse_download_percent("[download]  42.3% of   3.05MiB at  1.20MiB/s ETA 00:02\n"), Some(42.3));
+        assert_eq!(parse_download_percent("[download] 100% of    3.05MiB in 00:00:02 at 1.41MiB/s"), Some(100.0));
+        assert_eq!(parse_download_percent("[download]   7.0% of ~  10.00MiB at  2.00MiB/s ETA 00:05 (frag 1/20)"), Some(7.0));
+    }
+
+    #[test]
+    fn ignores_other_output() {
+        assert_eq!(parse_download_percent("[download] Destination: /tmp/100% real.mp4"), None);
+        assert_eq!(parse_download_percent("[youtube] dQw4w9WgXcQ: Downloading webpage"), None);
+        assert_eq!(parse_download_percent("[Merger] Merging formats into \"x.mp4\""), None);
+    }
+}
@@ -0,0 +1,185 @@
+use std::cell::Cell;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::time::Duration;
+use uuid::Uuid;
+use crate::config::{get_max_concurrent, get_shutdown_kill_grace};
+use crate::models::AppState;
+use ytdl_core::{extract_video_id, DownloadError, DownloadRequest, MediaType, Progress};
+use ytdl_core::process::terminate_children;
+use crate::services::build_downloader;
+use crate::utils::{find_executable, init_cli_logging};
+
+const USAGE: &str = "Usage: youtube-downloader fetch <url> [--type audio|video|merge] [--output <path>]";
+
+struct FetchArgs {
+    url: String,
+    media_type: MediaType,
+    output: Option<PathBuf>,
+}
+
+fn parse_fetch_args(args: &[String]) -> Result<FetchArgs, String> {
+    let mut url = None;
+    let mut media_type = MediaType::Merge;
+    let mut output = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-t" | "--type" => {
+                media_type = iter.next()
+                    .ok_or("--type requires a value")?
+                    .parse()
+                    .map_err(|e: DownloadError| e.message().to_string())?;
+            }
+            "-o" | "--output" => {
+                output = Some(PathBuf::from(iter.next().ok_or("--output requires a value")?));
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown option: {}", flag)),
+            value if url.is_none() => url = Some(value.to_string()),
+            value => return Err(format!("Unexpected argument: {}", value)),
+        }
+    }
+
+    Ok(FetchArgs {
+        url: url.ok_or("Missing <url>")?,
+        media_type,
+        output,
+    })
+}
+
+fn is_dir_target(path: &Path) -> bool {
+    path.is_dir() || path.to_string_lossy().ends_with(std::path::MAIN_SEPARATOR)
+}
+
+fn resolve_target(output: Option<&Path>, video_id: &str, downloaded: &Path) -> PathBuf {
+    let name = match downloaded.extension() {
+        Some(ext) => format!("{}.{}", video_id, ext.to_string_lossy()),
+        None => video_id.to_string(),
+    };
+
+    match output {
+        Some(path) if is_dir_target(path) => path.join(name),
+        Some(path) => path.to_path_buf(),
+        None => PathBuf::from(name),
+    }
+}
+
+pub async fn run(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("fetch") => fetch(&args[1..]).await,
+        _ => {
+            eprintln!("{}", USAGE);
+            2
+        }
+    }
+}
+
+async fn fetch(args: &[String]) -> i32 {
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let args = match parse_fetch_args(args) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            return 2;
+        }
+    };
+
+    init_cli_logging();
+
+    let video_id = match extract_video_id(&args.url) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let work_dir = match args.output.as_deref() {
+        Some(path) if is_dir_target(path) => path.to_path_buf(),
+        Some(path) => path.parent().filter(|p| !p.as_os_str().is_empty()).map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(".")),
+        None => PathBuf::from("."),
+    };
+    if let Err(e) = fs::create_dir_all(&work_dir).await {
+        eprintln!("Cannot create {}: {}", work_dir.display(), e);
+        return 1;
+    }
+
+    let max_concurrent = get_max_concurrent();
+    let state = Arc::new(AppState::new(max_concurrent, find_executable("ffmpeg"), find_executable("ffprobe")));
+
+    let interrupt_state = state.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("Interrupted, stopping yt-dlp...");
+            interrupt_state.shutting_down.store(true, Ordering::SeqCst);
+            terminate_children(Duration::from_secs(get_shutdown_kill_grace())).await;
+        }
+    });
+
+    let request = DownloadRequest::new(args.url.as_str(), args.media_type, work_dir).file_stem(format!(".{}", Uuid::new_v4()));
+
+    // On a terminal the percentage is redrawn in place; otherwise each whole
+    // percent gets its own line.
+    let interactive = std::io::stderr().is_terminal();
+    let shown: Cell<Option<(usize, u32)>> = Cell::new(None);
+    let end_line = || {
+        if shown.take().is_some() && interactive {
+            eprintln!();
+        }
+    };
+
+    eprintln!("Fetching {} ({})", video_id, args.media_type);
+    let result = build_downloader(state).download(&request, |progress| match progress {
+        Progress::Probing => eprintln!("Probing video info"),
+        Progress::Downloading { attempt, format } => {
+            end_line();
+            eprintln!("[attempt {}] downloading {}", attempt, format);
+        }
+        Progress::Percent { attempt, percent } => {
+            let whole = percent.floor() as u32;
+            if shown.replace(Some((attempt, whole))) != Some((attempt, whole)) {
+                if interactive {
+                    eprint!("\r[attempt {}] {:3}%", attempt, whole);
+                } else {
+                    eprintln!("[attempt {}] {}%", attempt, whole);
+                }
+            }
+        }
+        Progress::Output { .. } => {}
+        Progress::Processing => {
+            end_line();
+            eprintln!("Post-processing");
+        }
+    }).await;
+    end_line();
+
+    match result {
+        Ok(download) => {
+            let downloaded = download.file_path;
+            let target = resolve_target(args.output.as_deref(), &video_id, &downloaded);
+            if let Err(e) = fs::rename(&downloaded, &target).await {
+                eprintln!("Cannot write {}: {}", target.display(), e);
+                let _ = fs::remove_file(&downloaded).await;
+                return 1;
+            }
+            println!("{}", target.display());
+            0
+        }
+        Err(DownloadError::Cancelled) => {
+            eprintln!("Download interrupted");
+            130
+        }
+        Err(error) => {
+            eprintln!("Download failed: {} ({})", error.message(), error.code());
+            1
+        }
+    }
+}
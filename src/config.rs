@@ -1,85 +1,859 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::env;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+use tokio::time::{sleep, Duration};
+
+const PROXY_STRATEGIES: &[&str] = &["round_robin", "least_failures", "sticky"];
+const LOG_FORMATS: &[&str] = &["json", "pretty"];
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub port: u16,
+    pub downloads_dir: String,
+    pub audio_dir: String,
+    pub video_dir: String,
+    pub merge_dir: String,
+    pub cookies_dir: String,
+    pub max_concurrent: Option<usize>,
+    pub log_format: String,
+    pub log_level: String,
+    pub config_watch_interval: u64,
+
+    pub cache_duration: i64,
+    pub orphan_grace: u64,
+    pub completed_task_retention: i64,
+    pub failed_task_retention: i64,
+    pub task_log_max_bytes: usize,
+    pub max_video_duration: i64,
+    pub max_audio_duration: i64,
+    pub max_file_size: u64,
+    pub disk_space_margin: u64,
+    pub process_timeout: u64,
+    pub process_stall_timeout: u64,
+    pub process_cpu_limit: u64,
+    pub process_memory_limit: u64,
+    pub process_open_files_limit: u64,
+    pub derive_audio: bool,
+    pub derive_video: bool,
+    pub validate_outputs: bool,
+    pub duration_tolerance: f64,
+    pub pow_difficulty: usize,
+    pub valid_apikeys: Vec<String>,
+    pub admin_keys: Vec<String>,
+    pub apikey_priorities: HashMap<String, i64>,
+    pub premium_priority: i64,
+    pub priority_aging: u64,
+    pub reserved_premium_slots: usize,
+    pub max_tasks_per_ip: usize,
+    pub max_tasks_per_apikey: usize,
+    pub shed_queue_length: usize,
+    pub shed_cpu_load: f64,
+    pub shed_active_processes: usize,
+    pub shed_retry_after: u64,
+    pub use_cookies: bool,
+    pub cookie_quarantine_base: i64,
+    pub cookie_quarantine_max: i64,
+    pub cookie_expiry_warning: i64,
+    pub proxy: Option<String>,
+    pub proxies: Vec<String>,
+    pub proxy_strategy: String,
+    pub proxy_cookies: HashMap<String, String>,
+    pub proxy_failure_threshold: u32,
+    pub proxy_probe_interval: u64,
+    pub proxy_probe_url: String,
+    pub webhooks_dir: String,
+    pub webhook_secret: String,
+    pub webhook_max_attempts: u32,
+    pub webhook_timeout: u64,
+    pub webhook_allow_private: bool,
+    pub webhooks: HashMap<String, String>,
+    pub shutdown_timeout: u64,
+    pub shutdown_kill_grace: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            downloads_dir: "./downloads".to_string(),
+            audio_dir: "./downloads/audio".to_string(),
+            video_dir: "./downloads/video".to_string(),
+            merge_dir: "./downloads/merge".to_string(),
+            cookies_dir: "./cookies".to_string(),
+            max_concurrent: None,
+            log_format: "json".to_string(),
+            log_level: "info".to_string(),
+            config_watch_interval: 5,
+
+            cache_duration: 18000000,
+            orphan_grace: 3600,
+            completed_task_retention: 3600,
+            failed_task_retention: 300,
+            task_log_max_bytes: 16384,
+            max_video_duration: 10800,
+            max_audio_duration: 18000,
+            max_file_size: 1073741824,
+            disk_space_margin: 268435456,
+            process_timeout: 3600,
+            process_stall_timeout: 300,
+            process_cpu_limit: 0,
+            process_memory_limit: 0,
+            process_open_files_limit: 0,
+            derive_audio: true,
+            derive_video: false,
+            validate_outputs: true,
+            duration_tolerance: 3.0,
+            pow_difficulty: 1,
+            valid_apikeys: Vec::new(),
+            admin_keys: Vec::new(),
+            apikey_priorities: HashMap::new(),
+            premium_priority: 10,
+            priority_aging: 60,
+            reserved_premium_slots: 0,
+            max_tasks_per_ip: 3,
+            max_tasks_per_apikey: 20,
+            shed_queue_length: 100,
+            shed_cpu_load: 0.0,
+            shed_active_processes: 0,
+            shed_retry_after: 30,
+            use_cookies: true,
+            cookie_quarantine_base: 300,
+            cookie_quarantine_max: 21600,
+            cookie_expiry_warning: 604800,
+            proxy: None,
+            proxies: Vec::new(),
+            proxy_strategy: "round_robin".to_string(),
+            proxy_cookies: HashMap::new(),
+            proxy_failure_threshold: 3,
+            proxy_probe_interval: 60,
+            proxy_probe_url: "https://www.youtube.com/watch?v=jNQXAC9IVRw".to_string(),
+            webhooks_dir: "./downloads/webhooks".to_string(),
+            webhook_secret: String::new(),
+            webhook_max_attempts: 8,
+            webhook_timeout: 10,
+            webhook_allow_private: false,
+            webhooks: HashMap::new(),
+            shutdown_timeout: 30,
+            shutdown_kill_grace: 5,
+        }
+    }
+}
+
+fn env_value<T: FromStr>(name: &str, target: &mut T, errors: &mut Vec<String>) {
+    if let Ok(raw) = env::var(name) {
+        match raw.trim().parse() {
+            Ok(value) => *target = value,
+            Err(_) => errors.push(format!("{}: invalid value {:?}", name, raw)),
+        }
+    }
+}
+
+fn env_optional<T: FromStr>(name: &str, target: &mut Option<T>, errors: &mut Vec<String>) {
+    if let Ok(raw) = env::var(name) {
+        if raw.trim().is_empty() {
+            *target = None;
+        } else {
+            match raw.trim().parse() {
+                Ok(value) => *target = Some(value),
+                Err(_) => errors.push(format!("{}: invalid value {:?}", name, raw)),
+            }
+        }
+    }
+}
+
+fn env_list(name: &str, target: &mut Vec<String>) {
+    if let Ok(raw) = env::var(name) {
+        *target = raw.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect();
+    }
+}
+
+fn env_map<T: FromStr>(name: &str, target: &mut HashMap<String, T>, errors: &mut Vec<String>) {
+    let mut pairs = HashMap::new();
+    env_pairs(name, &mut pairs);
+    if env::var(name).is_err() {
+        return;
+    }
+
+    target.clear();
+    for (key, raw) in pairs {
+        match raw.parse() {
+            Ok(value) => {
+                target.insert(key, value);
+            }
+            Err(_) => errors.push(format!("{}: invalid value {:?} for {:?}", name, raw, key)),
+        }
+    }
+}
+
+fn env_pairs(name: &str, target: &mut HashMap<String, String>) {
+    if let Ok(raw) = env::var(name) {
+        *target = raw.split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+            .collect();
+    }
+}
+
+impl Config {
+    pub fn load() -> Result<Self, String> {
+        Self::load_with_pinned().map(|(config, _)| config)
+    }
+
+    /// Returns the file configuration and the keys the file sets explicitly.
+    fn from_file() -> Result<(Self, Vec<String>), String> {
+        let path = get_config_path();
+        if !Path::new(&path).exists() {
+            return Ok((Config::default(), Vec::new()));
+        }
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let config = toml::from_str(&content).map_err(|e| format!("{}: {}", path, e))?;
+        let keys = content.parse::<toml::Table>().map(|table| table.keys().cloned().collect()).unwrap_or_default();
+        Ok((config, keys))
+    }
+
+    /// Loads the effective configuration together with the environment
+    /// variables that override a value set in the config file.
+    fn load_with_pinned() -> Result<(Self, Vec<String>), String> {
+        let (file, keys) = Self::from_file()?;
+        let mut config = file.clone();
+
+        let mut errors = Vec::new();
+        config.apply_env(&mut errors);
+        errors.extend(config.validate());
+
+        if errors.is_empty() {
+            let pinned = config.env_pinned_keys(&file, &keys, |name| env::var(name).is_ok());
+            Ok((config, pinned))
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+
+    fn apply_env(&mut self, errors: &mut Vec<String>) {
+        env_value("PORT", &mut self.port, errors);
+        env_value("DOWNLOADS_DIR", &mut self.downloads_dir, errors);
+        env_value("AUDIO_DIR", &mut self.audio_dir, errors);
+        env_value("VIDEO_DIR", &mut self.video_dir, errors);
+        env_value("MERGE_DIR", &mut self.merge_dir, errors);
+        env_value("COOKIES_DIR", &mut self.cookies_dir, errors);
+        env_optional("MAX_CONCURRENT", &mut self.max_concurrent, errors);
+        env_value("LOG_FORMAT", &mut self.log_format, errors);
+        env_value("LOG_LEVEL", &mut self.log_level, errors);
+        env_value("CONFIG_WATCH_INTERVAL", &mut self.config_watch_interval, errors);
+
+        env_value("CACHE_DURATION", &mut self.cache_duration, errors);
+        env_value("ORPHAN_GRACE", &mut self.orphan_grace, errors);
+        env_value("COMPLETED_TASK_RETENTION", &mut self.completed_task_retention, errors);
+        env_value("FAILED_TASK_RETENTION", &mut self.failed_task_retention, errors);
+        env_value("TASK_LOG_MAX_BYTES", &mut self.task_log_max_bytes, errors);
+        env_value("MAX_VIDEO_DURATION", &mut self.max_video_duration, errors);
+        env_value("MAX_AUDIO_DURATION", &mut self.max_audio_duration, errors);
+        env_value("MAX_FILE_SIZE", &mut self.max_file_size, errors);
+        env_value("DISK_SPACE_MARGIN", &mut self.disk_space_margin, errors);
+        env_value("PROCESS_TIMEOUT", &mut self.process_timeout, errors);
+        env_value("PROCESS_STALL_TIMEOUT", &mut self.process_stall_timeout, errors);
+        env_value("PROCESS_CPU_LIMIT", &mut self.process_cpu_limit, errors);
+        env_value("PROCESS_MEMORY_LIMIT", &mut self.process_memory_limit, errors);
+        env_value("PROCESS_OPEN_FILES_LIMIT", &mut self.process_open_files_limit, errors);
+        env_value("DERIVE_AUDIO", &mut self.derive_audio, errors);
+        env_value("DERIVE_VIDEO", &mut self.derive_video, errors);
+        env_value("VALIDATE_OUTPUTS", &mut self.validate_outputs, errors);
+        env_value("DURATION_TOLERANCE", &mut self.duration_tolerance, errors);
+        env_value("POW_DIFFICULTY", &mut self.pow_difficulty, errors);
+        env_list("VALID_APIKEYS", &mut self.valid_apikeys);
+        env_list("ADMIN_KEYS", &mut self.admin_keys);
+        env_map("APIKEY_PRIORITIES", &mut self.apikey_priorities, errors);
+        env_value("PREMIUM_PRIORITY", &mut self.premium_priority, errors);
+        env_value("PRIORITY_AGING", &mut self.priority_aging, errors);
+        env_value("RESERVED_PREMIUM_SLOTS", &mut self.reserved_premium_slots, errors);
+        env_value("MAX_TASKS_PER_IP", &mut self.max_tasks_per_ip, errors);
+        env_value("MAX_TASKS_PER_APIKEY", &mut self.max_tasks_per_apikey, errors);
+        env_value("SHED_QUEUE_LENGTH", &mut self.shed_queue_length, errors);
+        env_value("SHED_CPU_LOAD", &mut self.shed_cpu_load, errors);
+        env_value("SHED_ACTIVE_PROCESSES", &mut self.shed_active_processes, errors);
+        env_value("SHED_RETRY_AFTER", &mut self.shed_retry_after, errors);
+        env_value("USE_COOKIES", &mut self.use_cookies, errors);
+        env_value("COOKIE_QUARANTINE_BASE", &mut self.cookie_quarantine_base, errors);
+        env_value("COOKIE_QUARANTINE_MAX", &mut self.cookie_quarantine_max, errors);
+        env_value("COOKIE_EXPIRY_WARNING", &mut self.cookie_expiry_warning, errors);
+        env_optional("PROXY", &mut self.proxy, errors);
+        env_list("PROXIES", &mut self.proxies);
+        env_value("PROXY_STRATEGY", &mut self.proxy_strategy, errors);
+        env_pairs("PROXY_COOKIES", &mut self.proxy_cookies);
+        env_value("PROXY_FAILURE_THRESHOLD", &mut self.proxy_failure_threshold, errors);
+        env_value("PROXY_PROBE_INTERVAL", &mut self.proxy_probe_interval, errors);
+        env_value("PROXY_PROBE_URL", &mut self.proxy_probe_url, errors);
+        env_value("WEBHOOKS_DIR", &mut self.webhooks_dir, errors);
+        env_value("WEBHOOK_SECRET", &mut self.webhook_secret, errors);
+        env_value("WEBHOOK_MAX_ATTEMPTS", &mut self.webhook_max_attempts, errors);
+        env_value("WEBHOOK_TIMEOUT", &mut self.webhook_timeout, errors);
+        env_value("WEBHOOK_ALLOW_PRIVATE", &mut self.webhook_allow_private, errors);
+        env_pairs("WEBHOOKS", &mut self.webhooks);
+        env_value("SHUTDOWN_TIMEOUT", &mut self.shutdown_timeout, errors);
+        env_value("SHUTDOWN_KILL_GRACE", &mut self.shutdown_kill_grace, errors);
+    }
+
+    /// Download slots in use: `max_concurrent`, or twice the CPU count within 4-32.
+    pub fn workers(&self) -> usize {
+        self.max_concurrent.unwrap_or_else(|| (num_cpus::get() * 2).clamp(4, 32))
+    }
+
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.port == 0 {
+            errors.push("port: must be greater than 0".to_string());
+        }
+        if self.max_concurrent == Some(0) {
+            errors.push("max_concurrent: must be greater than 0".to_string());
+        } else if self.reserved_premium_slots >= self.workers() {
+            errors.push(format!("reserved_premium_slots: must be lower than max_concurrent ({}) so free jobs can run", self.workers()));
+        }
+        if !LOG_FORMATS.contains(&self.log_format.as_str()) {
+            errors.push(format!("log_format: expected one of {:?}, found {:?}", LOG_FORMATS, self.log_format));
+        }
+        if self.cache_duration <= 0 {
+            errors.push("cache_duration: must be greater than 0".to_string());
+        }
+        if self.max_video_duration <= 0 || self.max_audio_duration <= 0 {
+            errors.push("max_video_duration/max_audio_duration: must be greater than 0".to_string());
+        }
+        if self.max_file_size == 0 {
+            errors.push("max_file_size: must be greater than 0".to_string());
+        }
+        if self.completed_task_retention < 0 || self.failed_task_retention < 0 {
+            errors.push("completed_task_retention/failed_task_retention: must be 0 or greater".to_string());
+        }
+        if self.duration_tolerance.is_nan() || self.duration_tolerance < 0.0 {
+            errors.push("duration_tolerance: must be 0 or greater".to_string());
+        }
+        if self.pow_difficulty == 0 || self.pow_difficulty > 8 {
+            errors.push(format!("pow_difficulty: expected 1-8, found {}", self.pow_difficulty));
+        }
+        if self.premium_priority <= 0 || self.apikey_priorities.values().any(|priority| *priority <= 0) {
+            errors.push("premium_priority/apikey_priorities: must be greater than 0 (free PoW traffic uses 0)".to_string());
+        }
+        if self.shed_cpu_load.is_nan() || self.shed_cpu_load < 0.0 {
+            errors.push("shed_cpu_load: must be 0 or greater".to_string());
+        }
+        if self.shed_retry_after == 0 {
+            errors.push("shed_retry_after: must be greater than 0".to_string());
+        }
+        if self.cookie_quarantine_base <= 0 || self.cookie_quarantine_max < self.cookie_quarantine_base {
+            errors.push("cookie_quarantine_base/cookie_quarantine_max: expected 0 < base <= max".to_string());
+        }
+        if !PROXY_STRATEGIES.contains(&self.proxy_strategy.as_str()) {
+            errors.push(format!("proxy_strategy: expected one of {:?}, found {:?}", PROXY_STRATEGIES, self.proxy_strategy));
+        }
+        if self.proxy_failure_threshold == 0 || self.proxy_probe_interval == 0 {
+            errors.push("proxy_failure_threshold/proxy_probe_interval: must be greater than 0".to_string());
+        }
+        if !self.webhooks.is_empty() && self.webhook_secret.is_empty() {
+            errors.push("webhook_secret: required when webhooks are configured".to_string());
+        }
+        if self.webhook_max_attempts == 0 || self.webhook_timeout == 0 {
+            errors.push("webhook_max_attempts/webhook_timeout: must be greater than 0".to_string());
+        }
+
+        errors
+    }
+
+    fn env_pinned_keys(&self, file: &Config, keys: &[String], is_set: impl Fn(&str) -> bool) -> Vec<String> {
+        let (effective, file) = (serde_json::to_value(self).unwrap_or_default(), serde_json::to_value(file).unwrap_or_default());
+
+        let mut pinned: Vec<String> = keys.iter()
+            .filter(|key| effective.get(key.as_str()) != file.get(key.as_str()))
+            .map(|key| key.to_uppercase())
+            .filter(|name| is_set(name))
+            .collect();
+        pinned.sort();
+        pinned
+    }
+
+    fn keep_restart_settings(&mut self, current: &Config) -> Vec<&'static str> {
+        let mut ignored = Vec::new();
+
+        macro_rules! keep {
+            ($($field:ident),*) => {
+                $(
+                    if self.$field != current.$field {
+                        ignored.push(stringify!($field));
+                        self.$field = current.$field.clone();
+                    }
+                )*
+            };
+        }
+
+        keep!(port, webhooks_dir, downloads_dir, audio_dir, video_dir, merge_dir, cookies_dir, max_concurrent, log_format, log_level, config_watch_interval);
+        ignored
+    }
+
+    pub fn redacted(&self) -> serde_json::Value {
+        let mut redacted = self.clone();
+        redacted.valid_apikeys = redacted.valid_apikeys.iter().map(|_| "***".to_string()).collect();
+        redacted.admin_keys = redacted.admin_keys.iter().map(|_| "***".to_string()).collect();
+        redacted.apikey_priorities = redacted.apikey_priorities.values()
+            .enumerate()
+            .map(|(i, priority)| (format!("key_{}", i + 1), *priority))
+            .collect();
+        redacted.proxy = redacted.proxy.as_deref().map(crate::utils::redact_proxy);
+        redacted.proxies = redacted.proxies.iter().map(|p| crate::utils::redact_proxy(p)).collect();
+        redacted.proxy_cookies = redacted.proxy_cookies.iter()
+            .map(|(cookie, proxy)| (cookie.clone(), crate::utils::redact_proxy(proxy)))
+            .collect();
+        if !redacted.webhook_secret.is_empty() {
+            redacted.webhook_secret = "***".to_string();
+        }
+        redacted.webhooks = redacted.webhooks.values()
+            .enumerate()
+            .map(|(i, url)| (format!("key_{}", i + 1), url.clone()))
+            .collect();
+        serde_json::to_value(redacted).unwrap_or_default()
+    }
+}
+
+static CONFIG: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+
+fn config_cell() -> &'static RwLock<Arc<Config>> {
+    CONFIG.get_or_init(|| RwLock::new(Arc::new(Config::load().unwrap_or_default())))
+}
+
+pub fn init() -> Result<(), String> {
+    let config = Config::load()?;
+    *config_cell().write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    Ok(())
+}
+
+pub fn config() -> Arc<Config> {
+    config_cell().read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub struct Reload {
+    pub ignored: Vec<&'static str>,
+    pub env_pinned: Vec<String>,
+}
+
+pub fn reload() -> Result<Reload, String> {
+    let (mut updated, env_pinned) = Config::load_with_pinned()?;
+    let ignored = updated.keep_restart_settings(&config());
+    *config_cell().write().unwrap_or_else(|e| e.into_inner()) = Arc::new(updated);
+    Ok(Reload { ignored, env_pinned })
+}
+
+fn reload_and_log(trigger: &str) {
+    match reload() {
+        Ok(reload) => {
+            for variable in &reload.env_pinned {
+                tracing::warn!(trigger, variable = %variable, "environment variable overrides the config file value; unset it to reload this key from the file");
+            }
+            if reload.ignored.is_empty() {
+                tracing::info!(trigger, "configuration reloaded");
+            } else {
+                tracing::warn!(trigger, ignored = ?reload.ignored, "configuration reloaded; some settings require a restart");
+            }
+        }
+        Err(e) => tracing::error!(trigger, error = %e, "configuration reload failed; keeping previous configuration"),
+    }
+}
+
+fn config_mtime() -> Option<SystemTime> {
+    std::fs::metadata(get_config_path()).and_then(|m| m.modified()).ok()
+}
+
+pub async fn watch_config() {
+    let interval = config().config_watch_interval;
+    let mut last_mtime = config_mtime();
+
+    #[cfg(unix)]
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()).ok();
+
+    loop {
+        #[cfg(unix)]
+        {
+            let hangup_received = async {
+                match hangup.as_mut() {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let poll = async {
+                if interval == 0 {
+                    std::future::pending::<()>().await
+                } else {
+                    sleep(Duration::from_secs(interval)).await
+                }
+            };
+
+            tokio::select! {
+                _ = hangup_received => {
+                    last_mtime = config_mtime();
+                    reload_and_log("sighup");
+                    continue;
+                }
+                _ = poll => {}
+            }
+        }
+
+        #[cfg(not(unix))]
+        {
+            if interval == 0 {
+                return;
+            }
+            sleep(Duration::from_secs(interval)).await;
+        }
+
+        let mtime = config_mtime();
+        if mtime != last_mtime {
+            last_mtime = mtime;
+            reload_and_log("file_change");
+        }
+    }
+}
+
+pub fn get_config_path() -> String {
+    env::var("CONFIG_FILE").unwrap_or_else(|_| "./config.toml".to_string())
+}
 
 pub fn get_downloads_dir() -> String {
-    env::var("DOWNLOADS_DIR").unwrap_or_else(|_| "./downloads".to_string())
+    config().downloads_dir.clone()
 }
 
 pub fn get_audio_dir() -> String {
-    env::var("AUDIO_DIR").unwrap_or_else(|_| "./downloads/audio".to_string())
+    config().audio_dir.clone()
 }
 
 pub fn get_video_dir() -> String {
-    env::var("VIDEO_DIR").unwrap_or_else(|_| "./downloads/video".to_string())
+    config().video_dir.clone()
 }
 
 pub fn get_merge_dir() -> String {
-    env::var("MERGE_DIR").unwrap_or_else(|_| "./downloads/merge".to_string())
+    config().merge_dir.clone()
 }
 
 pub fn get_cookies_dir() -> String {
-    env::var("COOKIES_DIR").unwrap_or_else(|_| "./cookies".to_string())
+    config().cookies_dir.clone()
 }
 
 pub fn get_cache_duration() -> i64 {
-    env::var("CACHE_DURATION")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(18000000)
+    config().cache_duration
 }
 
 pub fn get_max_video_duration() -> i64 {
-    env::var("MAX_VIDEO_DURATION")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(10800)
+    config().max_video_duration
 }
 
 pub fn get_max_audio_duration() -> i64 {
-    env::var("MAX_AUDIO_DURATION")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(18000)
+    config().max_audio_duration
 }
 
 pub fn get_max_file_size() -> u64 {
-    env::var("MAX_FILE_SIZE")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(1073741824)
+    config().max_file_size
+}
+
+pub fn get_disk_space_margin() -> u64 {
+    config().disk_space_margin
+}
+
+pub fn get_process_timeout() -> u64 {
+    config().process_timeout
+}
+
+pub fn get_process_stall_timeout() -> u64 {
+    config().process_stall_timeout
+}
+
+pub fn get_process_cpu_limit() -> u64 {
+    config().process_cpu_limit
+}
+
+pub fn get_process_memory_limit() -> u64 {
+    config().process_memory_limit
+}
+
+pub fn get_process_open_files_limit() -> u64 {
+    config().process_open_files_limit
 }
 
 pub fn get_pow_difficulty() -> usize {
-    env::var("POW_DIFFICULTY")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(1)
+    config().pow_difficulty
 }
 
 pub fn get_valid_apikeys() -> Vec<String> {
-    env::var("VALID_APIKEYS")
-        .ok()
-        .filter(|s| !s.trim().is_empty())
-        .map(|keys| keys.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
-        .unwrap_or_else(Vec::new)
+    config().valid_apikeys.clone()
+}
+
+pub fn get_admin_keys() -> Vec<String> {
+    config().admin_keys.clone()
+}
+
+pub fn get_apikey_priority(apikey: &str) -> i64 {
+    let config = config();
+    config.apikey_priorities.get(apikey).copied().unwrap_or(config.premium_priority)
+}
+
+pub fn get_priority_aging() -> u64 {
+    config().priority_aging
+}
+
+pub fn get_reserved_premium_slots() -> usize {
+    config().reserved_premium_slots
+}
+
+pub fn get_max_active_tasks(premium: bool) -> usize {
+    let config = config();
+    if premium { config.max_tasks_per_apikey } else { config.max_tasks_per_ip }
+}
+
+pub fn get_shed_queue_length() -> usize {
+    config().shed_queue_length
+}
+
+pub fn get_shed_cpu_load() -> f64 {
+    config().shed_cpu_load
+}
+
+pub fn get_shed_active_processes() -> usize {
+    config().shed_active_processes
+}
+
+pub fn get_shed_retry_after() -> u64 {
+    config().shed_retry_after
+}
+
+pub fn get_log_format() -> String {
+    config().log_format.clone()
+}
+
+pub fn get_log_level() -> String {
+    config().log_level.clone()
 }
 
 pub fn get_port() -> String {
-    env::var("PORT").unwrap_or_else(|_| "3000".to_string())
+    config().port.to_string()
+}
+
+pub fn get_max_concurrent() -> usize {
+    config().workers()
+}
+
+pub fn derive_audio() -> bool {
+    config().derive_audio
+}
+
+pub fn derive_video() -> bool {
+    config().derive_video
+}
+
+pub fn get_completed_task_retention() -> i64 {
+    config().completed_task_retention
+}
+
+pub fn get_failed_task_retention() -> i64 {
+    config().failed_task_retention
+}
+
+pub fn get_task_log_max_bytes() -> usize {
+    config().task_log_max_bytes
 }
 
-pub fn get_max_concurrent() -> Option<usize> {
-    env::var("MAX_CONCURRENT")
-        .ok()
-        .and_then(|v| v.parse().ok())
+pub fn get_orphan_grace() -> u64 {
+    config().orphan_grace
+}
+
+pub fn validate_outputs() -> bool {
+    config().validate_outputs
+}
+
+pub fn get_duration_tolerance() -> f64 {
+    config().duration_tolerance
 }
 
 pub fn use_cookies() -> bool {
-    env::var("USE_COOKIES")
-        .ok()
-        .and_then(|v| v.parse().ok())
-        .unwrap_or(true)
+    config().use_cookies
+}
+
+pub fn get_cookie_quarantine_base() -> i64 {
+    config().cookie_quarantine_base
+}
+
+pub fn get_cookie_quarantine_max() -> i64 {
+    config().cookie_quarantine_max
+}
+
+pub fn get_cookie_expiry_warning() -> i64 {
+    config().cookie_expiry_warning
 }
 
 pub fn get_proxy() -> Option<String> {
-    env::var("PROXY").ok().filter(|s| !s.is_empty())
+    config().proxy.clone().filter(|s| !s.is_empty())
+}
+
+pub fn get_proxies() -> Vec<String> {
+    let proxies = config().proxies.clone();
+
+    if proxies.is_empty() {
+        get_proxy().into_iter().collect()
+    } else {
+        proxies
+    }
+}
+
+pub fn get_proxy_strategy() -> String {
+    config().proxy_strategy.clone()
+}
+
+pub fn get_proxy_cookie_pairs() -> HashMap<String, String> {
+    config().proxy_cookies.clone()
+}
+
+pub fn get_proxy_failure_threshold() -> u32 {
+    config().proxy_failure_threshold
+}
+
+pub fn get_proxy_probe_interval() -> u64 {
+    config().proxy_probe_interval
+}
+
+pub fn get_proxy_probe_url() -> String {
+    config().proxy_probe_url.clone()
+}
+
+pub fn get_webhooks_dir() -> String {
+    config().webhooks_dir.clone()
+}
+
+pub fn get_webhook_secret() -> String {
+    config().webhook_secret.clone()
+}
+
+pub fn get_webhook_max_attempts() -> u32 {
+    config().webhook_max_attempts
+}
+
+pub fn get_webhook_allow_private() -> bool {
+    config().webhook_allow_private
+}
+
+pub fn get_webhook_timeout() -> u64 {
+    config().webhook_timeout
+}
+
+pub fn get_apikey_webhook(apikey: &str) -> Option<String> {
+    config().webhooks.get(apikey).cloned()
+}
+
+pub fn get_shutdown_timeout() -> u64 {
+    config().shutdown_timeout
+}
+
+pub fn get_shutdown_kill_grace() -> u64 {
+    config().shutdown_kill_grace
+}
+
+pub fn get_pending_tasks_file() -> String {
+    format!("{}/pending_tasks.json", get_downloads_dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Config::default().validate().is_empty());
+    }
+
+    #[test]
+    fn rejects_out_of_range_values() {
+        let config = Config {
+            port: 0,
+            max_concurrent: Some(0),
+            log_format: "xml".to_string(),
+            pow_difficulty: 9,
+            proxy_strategy: "random".to_string(),
+            duration_tolerance: f64::NAN,
+            ..Config::default()
+        };
+
+        let errors = config.validate();
+        for field in ["port", "max_concurrent", "log_format", "pow_difficulty", "proxy_strategy", "duration_tolerance"] {
+            assert!(errors.iter().any(|error| error.starts_with(field)), "no error for {}: {:?}", field, errors);
+        }
+        assert_eq!(errors.len(), 6);
+    }
+
+    #[test]
+    fn rejects_inconsistent_combinations() {
+        let mut config = Config::default();
+        config.webhooks.insert("k1".to_string(), "https://example.com/hook".to_string());
+        config.cookie_quarantine_base = 600;
+        config.cookie_quarantine_max = 60;
+        config.apikey_priorities.insert("k1".to_string(), 0);
+
+        let errors = config.validate();
+        assert!(errors.iter().any(|error| error.starts_with("webhook_secret")));
+        assert!(errors.iter().any(|error| error.starts_with("cookie_quarantine_base")));
+        assert!(errors.iter().any(|error| error.starts_with("premium_priority/apikey_priorities")));
+    }
+
+    #[test]
+    fn reserved_slots_must_leave_room_for_free_jobs() {
+        let mut config = Config {
+            max_concurrent: Some(4),
+            reserved_premium_slots: 4,
+            ..Config::default()
+        };
+        let errors = config.validate();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("reserved_premium_slots"));
+
+        config.reserved_premium_slots = 3;
+        assert!(config.validate().is_empty());
+
+        config.max_concurrent = None;
+        config.reserved_premium_slots = config.workers();
+        assert!(config.validate().iter().any(|error| error.starts_with("reserved_premium_slots")));
+    }
+
+    #[test]
+    fn example_config_is_complete_and_valid() {
+        let example: Config = toml::from_str(include_str!("../config.example.toml")).unwrap();
+        assert!(example.validate().is_empty());
+        assert_eq!((example.shutdown_timeout, example.shutdown_kill_grace), (30, 5));
+        assert_eq!(example.webhook_max_attempts, Config::default().webhook_max_attempts);
+    }
+
+    #[test]
+    fn unknown_keys_are_rejected() {
+        assert!(toml::from_str::<Config>("valid_apikey = [\"k1\"]").is_err());
+        let config: Config = toml::from_str("valid_apikeys = [\"k1\"]\npow_difficulty = 3").unwrap();
+        assert_eq!(config.valid_apikeys, vec!["k1"]);
+        assert_eq!(config.pow_difficulty, 3);
+    }
+
+    #[test]
+    fn env_overrides_are_reported_for_keys_set_in_the_file() {
+        let file = Config {
+            proxy_probe_url: "https://file.example".to_string(),
+            ..Config::default()
+        };
+        let keys = vec!["proxy_probe_url".to_string(), "shed_retry_after".to_string()];
+        let env = ["PROXY_PROBE_URL", "SHED_RETRY_AFTER"];
+        let is_set = |name: &str| env.contains(&name);
+        assert!(file.clone().env_pinned_keys(&file, &keys, is_set).is_empty());
+
+        let effective = Config {
+            proxy_probe_url: "https://env.example".to_string(),
+            ..file.clone()
+        };
+        assert_eq!(effective.env_pinned_keys(&file, &keys, is_set), vec!["PROXY_PROBE_URL"]);
+        assert!(effective.env_pinned_keys(&file, &keys, |_| false).is_empty());
+    }
 }
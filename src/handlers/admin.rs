@@ -0,0 +1,110 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_files::NamedFile;
+use tokio::fs;
+use crate::models::{AppState, ApiError};
+use crate::config::{config, get_cookies_dir};
+use crate::middleware::is_admin_request;
+use crate::services::{cookie_report, parse_netscape_cookies};
+use crate::utils::is_cookie_filename;
+
+fn require_admin(req: &HttpRequest) -> Result<(), ApiError> {
+    if is_admin_request(req) {
+        Ok(())
+    } else {
+        Err(ApiError::forbidden("admin_required", "Admin key required"))
+    }
+}
+
+fn cookie_name(path: web::Path<String>) -> Result<String, ApiError> {
+    let name = path.into_inner();
+    if is_cookie_filename(&name) {
+        Ok(name)
+    } else {
+        Err(ApiError::bad_request("invalid_cookie_name", "Invalid cookie file name. Expected cookies*.txt"))
+    }
+}
+
+fn cookie_not_found() -> ApiError {
+    ApiError::not_found("cookie_not_found", "Cookie file not found")
+}
+
+pub async fn get_config(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    require_admin(&req)?;
+
+    Ok(HttpResponse::Ok().json(config().redacted()))
+}
+
+pub async fn list_cookies(state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    require_admin(&req)?;
+
+    Ok(HttpResponse::Ok().json(cookie_report(&state)))
+}
+
+pub async fn upload_cookies(
+    path: web::Path<String>,
+    body: web::Bytes,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_admin(&req)?;
+    let name = cookie_name(path)?;
+
+    let content = std::str::from_utf8(&body)
+        .map_err(|_| ApiError::bad_request("invalid_cookie_file", "Cookie file must be UTF-8 text"))?;
+
+    let summary = parse_netscape_cookies(content).map_err(|e| {
+        ApiError::bad_request("invalid_cookie_file", "Invalid Netscape cookie file")
+            .with_details(serde_json::json!({"detail": e}))
+    })?;
+
+    let cookies_dir = get_cookies_dir();
+    let target = format!("{}/{}", cookies_dir, name);
+    let tmp = format!("{}/.{}.upload", cookies_dir, name);
+
+    fs::write(&tmp, content).await?;
+    fs::rename(&tmp, &target).await?;
+
+    if let Ok(canonical) = fs::canonicalize(&target).await {
+        state.cookie_health.remove(&canonical.to_string_lossy().to_string());
+    }
+
+    Ok(HttpResponse::Created().json(serde_json::json!({
+        "file": name,
+        "cookies": summary.cookies,
+        "login_cookies": summary.login_cookies,
+        "expires_at": summary.expires_at
+    })))
+}
+
+pub async fn download_cookies(path: web::Path<String>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    require_admin(&req)?;
+    let name = cookie_name(path)?;
+
+    let file = NamedFile::open_async(format!("{}/{}", get_cookies_dir(), name))
+        .await
+        .map_err(|_| cookie_not_found())?;
+
+    Ok(file
+        .set_content_disposition(actix_web::http::header::ContentDisposition {
+            disposition: actix_web::http::header::DispositionType::Attachment,
+            parameters: vec![],
+        })
+        .into_response(&req))
+}
+
+pub async fn delete_cookies(
+    path: web::Path<String>,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    require_admin(&req)?;
+    let name = cookie_name(path)?;
+
+    let target = format!("{}/{}", get_cookies_dir(), name);
+    let canonical = fs::canonicalize(&target).await.map_err(|_| cookie_not_found())?;
+
+    fs::remove_file(&canonical).await?;
+    state.cookie_health.remove(&canonical.to_string_lossy().to_string());
+
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,14 +1,37 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse};
 use chrono::Utc;
-use crate::models::{ChallengeRequest, ChallengeResponse, PowChallenge, AppState};
+use std::path::Path;
+use crate::models::{ChallengeRequest, ChallengeResponse, PowChallenge, AppState, ApiError};
 use crate::config::get_pow_difficulty;
+use crate::services::content_key;
+use crate::services::load::shed_load;
 use crate::utils::{get_real_ip, get_client_identifier, generate_challenge};
+use ytdl_core::extract_video_id;
 
+#[utoipa::path(
+    post,
+    path = "/v1/challenge",
+    tag = "auth",
+    request_body = ChallengeRequest,
+    responses(
+        (status = 200, description = "PoW challenge issued", body = ChallengeResponse),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 503, description = "Server is overloaded", body = ApiError),
+    )
+)]
 pub async fn akumaudownload(
     body: web::Json<ChallengeRequest>,
     state: web::Data<AppState>,
     req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
+    let cached = extract_video_id(&body.url).is_ok_and(|video_id| {
+        let cache_key = format!("{:x}", md5::compute(content_key(&video_id, &body.r#type)));
+        state.cache.get(&cache_key).is_some_and(|entry| Path::new(&entry.file_path).exists())
+    });
+    if !cached {
+        shed_load(&state)?;
+    }
+
     let ip = get_real_ip(&req);
     
     let user_agent = req.headers()
@@ -32,6 +55,8 @@ pub async fn akumaudownload(
         },
     );
     
+    state.metrics.pow_issued.inc();
+
     Ok(HttpResponse::Ok().json(ChallengeResponse {
         challenge,
         difficulty,
@@ -1,119 +1,186 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use chrono::Utc;
 use std::path::PathBuf;
-use crate::models::{DownloadQuery, Task, AppState};
-use crate::services::{extract_video_id, process_download};
-use crate::middleware::is_valid_apikey;
-use crate::utils::get_real_ip;
+use std::sync::atomic::Ordering;
+use crate::models::{DownloadQuery, DownloadResponse, Task, AppState, ApiError, CachedDownloadResponse};
+use ytdl_core::extract_video_id;
+use uuid::Uuid;
+use crate::services::{content_key, join_or_spawn_job, is_valid_callback_url, register_task_webhooks};
+use crate::services::load::shed_load;
+use crate::services::scheduler::{Requester, FREE_PRIORITY};
+use crate::config::{get_apikey_priority, get_max_active_tasks, get_webhook_secret};
+use crate::middleware::{is_valid_apikey, RequestId};
+use crate::utils::{get_real_ip, apikey_owner, ip_owner, session_owner};
 
-pub async fn download(query: web::Query<DownloadQuery>, state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse> {
+pub async fn download(query: web::Query<DownloadQuery>, state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    start_download(query.into_inner(), state, req).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/v1/downloads",
+    tag = "downloads",
+    request_body = DownloadQuery,
+    responses(
+        (status = 200, description = "Cached file or existing task", body = DownloadResponse),
+        (status = 202, description = "Task accepted", body = Task),
+        (status = 400, description = "Invalid request", body = ApiError),
+        (status = 401, description = "PoW challenge required", body = ApiError),
+        (status = 403, description = "Session does not match this task", body = ApiError),
+        (status = 429, description = "Too many active tasks for this client", body = ApiError),
+        (status = 503, description = "Server is shutting down or overloaded", body = ApiError),
+    )
+)]
+pub async fn create_download(body: web::Json<DownloadQuery>, state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    start_download(body.into_inner(), state, req).await
+}
+
+async fn start_download(query: DownloadQuery, state: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
     match query.r#type.as_str() {
         "audio" | "video" | "merge" => (),
         _ => {
-            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Invalid media type. Allowed: audio, video, merge",
-                "received": query.r#type
-            })));
+            return Err(ApiError::bad_request("invalid_media_type", "Invalid media type. Allowed: audio, video, merge")
+                .with_details(serde_json::json!({"received": query.r#type})));
         }
     }
 
-    let is_premium = query.apikey.as_ref().map_or(false, |key| is_valid_apikey(key));
+    let is_premium = query.apikey.as_ref().is_some_and(|key| is_valid_apikey(key));
 
-    let video_id = match extract_video_id(&query.url) {
-        Ok(id) => id,
-        Err(_) => {
-            return Ok(HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid YouTube URL"})));
+    if let Some(callback_url) = &query.callback_url {
+        if !is_premium {
+            return Err(ApiError::forbidden("callback_requires_apikey", "callback_url requires a valid API key"));
         }
-    };
+        if get_webhook_secret().is_empty() {
+            return Err(ApiError::bad_request("webhooks_disabled", "Webhooks are not configured on this server"));
+        }
+        if !is_valid_callback_url(callback_url) {
+            return Err(ApiError::bad_request("invalid_callback_url", "Invalid callback_url. Expected a public http or https URL"));
+        }
+    }
 
-    let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, query.r#type)));
+    let webhook_apikey = if is_premium { query.apikey.as_deref() } else { None };
+
+    let video_id = extract_video_id(&query.url)
+        .map_err(|_| ApiError::bad_request("invalid_url", "Invalid YouTube URL"))?;
+
+    let key = content_key(&video_id, &query.r#type);
+    let cache_key = format!("{:x}", md5::compute(&key));
     
     if let Some(entry) = state.cache.get(&cache_key) {
         if PathBuf::from(&entry.file_path).exists() {
             if !is_premium {
                 let session_cookie = req.cookie("pow_session");
                 if session_cookie.is_none() {
-                    return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
-                        "error": "PoW challenge required",
-                        "action": "get_challenge"
-                    })));
+                    return Err(ApiError::pow_required());
                 }
             }
+            state.metrics.cache_hits.inc();
             
             let file_url = format!("/files/{}", PathBuf::from(&entry.file_path).file_name().unwrap().to_string_lossy());
-            return Ok(HttpResponse::Ok().json(serde_json::json!({
-                "status": "completed",
-                "fileUrl": file_url,
-                "cached": true
-            })));
+            return Ok(HttpResponse::Ok().json(DownloadResponse::Cached(Box::new(CachedDownloadResponse {
+                status: "completed".to_string(),
+                file_url,
+                cached: true,
+                media_info: entry.media_info.clone(),
+            }))));
         } else {
             state.cache.remove(&cache_key);
         }
     }
 
-    if !is_premium {
-        let session_cookie = req.cookie("pow_session");
-        
-        if let Some(cookie) = session_cookie {
-            let session_token = cookie.value().to_string();
-            
-            let ip = get_real_ip(&req);
-            
-            let user_agent = req.headers()
-                .get("user-agent")
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("unknown")
-                .to_string();
-            
-            let task_id = format!("{}_{}", video_id, query.r#type);
-            
-            match state.pow_sessions.get(&session_token) {
-                Some(session) => {
-                    if session.task_id != task_id || session.ip != ip || session.user_agent != user_agent {
-                        return Ok(HttpResponse::Forbidden().json(serde_json::json!({
-                            "error": "Invalid session for this task"
-                        })));
-                    }
-                }
-                None => {
-                    return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
-                        "error": "PoW challenge required",
-                        "action": "get_challenge"
-                    })));
+    let (owner, client) = if is_premium {
+        let owner = apikey_owner(query.apikey.as_deref().unwrap_or_default());
+        (owner.clone(), owner)
+    } else {
+        let session_token = req.cookie("pow_session")
+            .map(|cookie| cookie.value().to_string())
+            .ok_or_else(ApiError::pow_required)?;
+
+        let ip = get_real_ip(&req);
+
+        let user_agent = req.headers()
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("unknown")
+            .to_string();
+
+        match state.pow_sessions.get(&session_token) {
+            Some(session) => {
+                if session.content_key != key || session.ip != ip || session.user_agent != user_agent {
+                    return Err(ApiError::forbidden("invalid_session", "Invalid session for this task"));
                 }
             }
-        } else {
-            return Ok(HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "PoW challenge required",
-                "action": "get_challenge"
-            })));
+            None => return Err(ApiError::pow_required()),
+        }
+
+        (session_owner(&session_token), ip_owner(&ip))
+    };
+
+    let existing = state.tasks.iter()
+        .filter(|task| task.owner == owner && task.content_key == key)
+        .filter(|task| match task.status.as_str() {
+            "queued" | "downloading" | "processing" => true,
+            "completed" => PathBuf::from(&task.file_path).exists(),
+            _ => false,
+        })
+        .max_by_key(|task| task.created_at)
+        .map(|task| task.clone());
+
+    if let Some(task) = existing {
+        if task.status != "completed" {
+            register_task_webhooks(&state, &task.id, query.callback_url.as_deref(), webhook_apikey);
         }
+        return Ok(HttpResponse::Ok().json(DownloadResponse::Task(Box::new(task))));
     }
 
-    let task_id = format!("{}_{}", video_id, query.r#type);
-    
-    if let Some(task) = state.tasks.get(&task_id) {
-        return Ok(HttpResponse::Ok().json(task.clone()));
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err(ApiError::unavailable("shutting_down", "Server is shutting down", 30));
+    }
+    shed_load(&state)?;
+
+    let priority = match query.apikey.as_deref().filter(|_| is_premium) {
+        Some(apikey) => get_apikey_priority(apikey),
+        None => FREE_PRIORITY,
+    };
+
+    let admission = state.task_admission.lock().unwrap_or_else(|e| e.into_inner());
+    let limit = get_max_active_tasks(is_premium);
+    let active = state.tasks.iter()
+        .filter(|task| task.client == client && matches!(task.status.as_str(), "queued" | "downloading" | "processing"))
+        .count();
+    if limit > 0 && active >= limit {
+        return Err(ApiError::too_many_requests("too_many_tasks", "Too many active downloads for this client", 10)
+            .with_details(serde_json::json!({"limit": limit, "active": active})));
     }
+    state.metrics.cache_misses.inc();
 
+    let task_id = Uuid::new_v4().to_string();
     let task = Task {
         id: task_id.clone(),
         url: query.url.clone(),
         media_type: query.r#type.clone(),
-        status: "downloading".to_string(),
-        progress: "50%".to_string(),
+        status: "queued".to_string(),
+        progress: "0%".to_string(),
         file_path: String::new(),
         file_url: String::new(),
         created_at: Utc::now().timestamp_millis(),
         error: String::new(),
+        error_code: String::new(),
+        finished_at: 0,
+        owner,
+        content_key: key,
+        priority,
+        client: client.clone(),
+        media_info: None,
     };
 
     state.tasks.insert(task_id.clone(), task.clone());
+    drop(admission);
+    register_task_webhooks(&state, &task_id, query.callback_url.as_deref(), webhook_apikey);
 
-    let state_clone = state.clone();
-    tokio::spawn(async move {
-        process_download(state_clone, video_id, query.url.clone(), query.r#type.clone()).await;
-    });
+    let request_id = req.extensions().get::<RequestId>().map(|r| r.0.clone()).unwrap_or_default();
+    join_or_spawn_job(&state, &task_id, &video_id, &query.url, &query.r#type, Requester { client, priority }, &request_id);
 
+    let task = state.tasks.get(&task_id).map(|task| task.clone()).unwrap_or(task);
     Ok(HttpResponse::Accepted().json(task))
 }
@@ -1,14 +1,30 @@
-use actix_web::{web, HttpRequest, Result};
+use actix_web::{web, HttpRequest};
 use actix_files::NamedFile;
 use tokio::fs;
+use crate::models::ApiError;
 use crate::config::{get_audio_dir, get_video_dir, get_merge_dir};
 use crate::utils::is_safe_filename;
 
-pub async fn serve_file(path: web::Path<String>, _req: HttpRequest) -> Result<NamedFile> {
+#[utoipa::path(
+    get,
+    path = "/v1/files/{filename}",
+    tag = "files",
+    params(("filename" = String, Path, description = "File name from a task's file_url")),
+    responses(
+        (status = 200, description = "Media file", content_type = "application/octet-stream"),
+        (status = 400, description = "Invalid filename", body = ApiError),
+        (status = 404, description = "File not found", body = ApiError),
+    )
+)]
+pub async fn serve_file(path: web::Path<String>, _req: HttpRequest) -> Result<NamedFile, ApiError> {
     let filename = path.into_inner();
     
     if !is_safe_filename(&filename) {
-        return Err(actix_web::error::ErrorBadRequest("Invalid filename"));
+        return Err(ApiError::bad_request("invalid_filename", "Invalid filename"));
+    }
+
+    if filename.starts_with('.') || filename.ends_with(".json") {
+        return Err(ApiError::not_found("file_not_found", "File not found"));
     }
     
     let dirs = vec![get_audio_dir(), get_video_dir(), get_merge_dir()];
@@ -20,7 +36,7 @@ pub async fn serve_file(path: web::Path<String>, _req: HttpRequest) -> Result<Na
             let dir_canonical = fs::canonicalize(dir).await.unwrap_or_default();
             
             if !canonical.starts_with(&dir_canonical) {
-                return Err(actix_web::error::ErrorBadRequest("Invalid file path"));
+                return Err(ApiError::bad_request("invalid_filename", "Invalid file path"));
             }
             
             if canonical.is_file() {
@@ -36,5 +52,5 @@ pub async fn serve_file(path: web::Path<String>, _req: HttpRequest) -> Result<Na
         }
     }
     
-    Err(actix_web::error::ErrorNotFound("File not found"))
+    Err(ApiError::not_found("file_not_found", "File not found"))
 }
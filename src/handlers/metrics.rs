@@ -0,0 +1,17 @@
+use actix_web::{web, HttpResponse, Result};
+use crate::models::AppState;
+
+pub async fn metrics(state: web::Data<AppState>) -> Result<HttpResponse> {
+    let metrics = &state.metrics;
+
+    for task_state in ["queued", "downloading", "processing", "completed", "failed", "interrupted"] {
+        let count = state.tasks.iter().filter(|t| t.status == task_state).count();
+        metrics.tasks.with_label_values(&[task_state]).set(count as i64);
+    }
+
+    metrics.queue_depth.set(state.scheduler.snapshot().queued as i64);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(metrics.encode()))
+}
@@ -3,9 +3,17 @@ pub mod verify;
 pub mod status;
 pub mod download;
 pub mod files;
+pub mod admin;
+pub mod metrics;
+pub mod tasks;
+pub mod openapi;
 
 pub use challenge::*;
 pub use verify::*;
 pub use status::*;
 pub use download::*;
 pub use files::*;
+pub use admin::*;
+pub use metrics::*;
+pub use tasks::*;
+pub use openapi::*;
@@ -0,0 +1,30 @@
+use actix_web::HttpResponse;
+use utoipa::OpenApi;
+use crate::models::{
+    ApiError, CacheInfo, CachedDownloadResponse, DownloadResponse, ChallengeRequest, ChallengeResponse, CookieFileInfo, CookieInfo,
+    DiskInfo, DiskUsage, DownloadQuery, ProxyInfo, ProxyStatus, LoadInfo, SchedulerInfo, StatusResponse, SystemInfo, Task, TaskInfo, TaskListResponse, TaskLogEntry, TaskLogResponse, VerifyRequest,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "YouTube Downloader API", version = "1"),
+    paths(
+        crate::handlers::challenge::akumaudownload,
+        crate::handlers::verify::cekpunyaku,
+        crate::handlers::download::create_download,
+        crate::handlers::tasks::get_task,
+        crate::handlers::tasks::get_task_logs,
+        crate::handlers::tasks::list_tasks,
+        crate::handlers::files::serve_file,
+        crate::handlers::status::status,
+    ),
+    components(schemas(
+        ApiError, CacheInfo, CachedDownloadResponse, DownloadResponse, ChallengeRequest, ChallengeResponse, CookieFileInfo, CookieInfo,
+        DiskInfo, DiskUsage, DownloadQuery, ProxyInfo, ProxyStatus, LoadInfo, SchedulerInfo, StatusResponse, SystemInfo, Task, TaskInfo, TaskListResponse, TaskLogEntry, TaskLogResponse, VerifyRequest,
+    ))
+)]
+pub struct ApiDoc;
+
+pub async fn openapi_json() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
@@ -1,28 +1,73 @@
-use actix_web::{web, HttpResponse, Result};
-use crate::models::{StatusResponse, SystemInfo, CacheInfo, TaskInfo, AppState};
-use crate::utils::helpers::get_available_cookies;
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use std::path::Path;
+use crate::models::{ApiError, StatusResponse, SystemInfo, CacheInfo, DiskInfo, DiskUsage, LoadInfo, SchedulerInfo, TaskInfo, CookieInfo, ProxyInfo, AppState};
+use crate::services::{cookie_report, media_dir, proxy_report};
+use crate::services::load::load_snapshot;
+use crate::config::{get_disk_space_margin, get_proxy_strategy, get_shed_active_processes, get_shed_cpu_load, get_shed_queue_length};
+use ytdl_core::{disk_space, MediaType};
 
-pub async fn status(state: web::Data<AppState>) -> Result<HttpResponse> {
+#[utoipa::path(
+    get,
+    path = "/v1/status",
+    tag = "status",
+    responses((status = 200, description = "Server status", body = StatusResponse))
+)]
+pub async fn status(state: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
     let uptime = state.start_time.elapsed();
     let total = state.tasks.len();
 
-    let (downloading, processing, completed, failed) = state.tasks.iter()
-        .fold((0, 0, 0, 0), |(d, p, c, f), entry| {
+    let (queued, downloading, processing, completed, failed) = state.tasks.iter()
+        .fold((0, 0, 0, 0, 0), |(q, d, p, c, f), entry| {
             match entry.status.as_str() {
-                "downloading" => (d + 1, p, c, f),
-                "processing" => (d, p + 1, c, f),
-                "completed" => (d, p, c + 1, f),
-                "failed" => (d, p, c, f + 1),
-                _ => (d, p, c, f),
+                "queued" => (q + 1, d, p, c, f),
+                "downloading" => (q, d + 1, p, c, f),
+                "processing" => (q, d, p + 1, c, f),
+                "completed" => (q, d, p, c + 1, f),
+                "failed" => (q, d, p, c, f + 1),
+                _ => (q, d, p, c, f),
             }
         });
+    let scheduler = state.scheduler.snapshot();
+    let load = load_snapshot(&state);
 
-    let cookies_count = get_available_cookies().len();
-    let active_cookies = if cookies_count > 0 {
-        format!("{} files", cookies_count)
-    } else {
-        "none".to_string()
-    };
+    let cookie_files = cookie_report(&state);
+    let healthy_cookies = cookie_files.iter().filter(|c| c.healthy).count();
+    let cookie_warnings: Vec<String> = cookie_files.iter()
+        .filter_map(|c| {
+            if !c.valid {
+                Some(format!("{} is not a valid Netscape cookie file", c.file))
+            } else if c.expiring_soon {
+                let expires_at = c.expires_at.unwrap_or_default();
+                let when = chrono::DateTime::from_timestamp(expires_at, 0)
+                    .map(|t| t.to_rfc3339())
+                    .unwrap_or_default();
+                if expires_at <= Utc::now().timestamp() {
+                    Some(format!("{} login cookies expired at {}", c.file, when))
+                } else {
+                    Some(format!("{} login cookies expire at {}", c.file, when))
+                }
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let disk_dirs = [MediaType::Audio, MediaType::Video, MediaType::Merge].into_iter()
+        .map(|media_type| {
+            let dir = media_dir(media_type);
+            let space = disk_space(Path::new(&dir));
+            DiskUsage {
+                media_type: media_type.to_string(),
+                dir,
+                free_bytes: space.map(|s| s.available),
+                total_bytes: space.map(|s| s.total),
+            }
+        })
+        .collect();
+
+    let proxy_entries = proxy_report(&state);
+    let proxies_up = proxy_entries.iter().filter(|p| p.up).count();
 
     Ok(HttpResponse::Ok().json(StatusResponse {
         status: "ok".to_string(),
@@ -34,17 +79,55 @@ pub async fn status(state: web::Data<AppState>) -> Result<HttpResponse> {
             max_concurrent: state.max_concurrent,
             ffmpeg: state.ffmpeg_path.clone(),
             ffprobe: state.ffprobe_path.clone(),
-            active_cookies,
+            active_cookies: match cookie_files.len() {
+                0 => "none".to_string(),
+                count => format!("{} files", count),
+            },
         },
         cache: CacheInfo {
             total: state.cache.len(),
         },
+        disk: DiskInfo {
+            margin: get_disk_space_margin(),
+            dirs: disk_dirs,
+        },
+        scheduler: SchedulerInfo {
+            workers: scheduler.workers,
+            reserved_premium: scheduler.reserved_premium,
+            running: scheduler.running,
+            queued: scheduler.queued,
+        },
+        load: LoadInfo {
+            shedding: load.shedding(),
+            reasons: load.reasons.iter().map(|reason| reason.to_string()).collect(),
+            queued: load.queued,
+            cpu_load: load.cpu_load,
+            active_processes: load.active_processes,
+            max_queue_length: get_shed_queue_length(),
+            max_cpu_load: get_shed_cpu_load(),
+            max_active_processes: get_shed_active_processes(),
+        },
         tasks: TaskInfo {
             total,
+            queued,
             downloading,
             processing,
             completed,
             failed,
         },
+        cookies: CookieInfo {
+            total: cookie_files.len(),
+            healthy: healthy_cookies,
+            quarantined: cookie_files.len() - healthy_cookies,
+            warnings: cookie_warnings,
+            files: cookie_files,
+        },
+        proxies: ProxyInfo {
+            strategy: get_proxy_strategy(),
+            total: proxy_entries.len(),
+            up: proxies_up,
+            down: proxy_entries.len() - proxies_up,
+            entries: proxy_entries,
+        },
     }))
 }
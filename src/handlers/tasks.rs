@@ -0,0 +1,138 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use crate::models::{AppState, ApiError, Task, TaskLogResponse, TaskLookupQuery, TaskListQuery, TaskListResponse};
+use crate::middleware::{is_admin_request, is_valid_apikey};
+use crate::utils::{apikey_owner, session_owner};
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+enum Scope {
+    All,
+    Owner(String),
+}
+
+fn session_requester(req: &HttpRequest, state: &AppState) -> Option<String> {
+    let cookie = req.cookie("pow_session")?;
+    state.pow_sessions.contains_key(cookie.value()).then(|| session_owner(cookie.value()))
+}
+
+fn requester_scope(req: &HttpRequest, state: &AppState, apikey: Option<&str>) -> Result<Scope, ApiError> {
+    if is_admin_request(req) {
+        return Ok(Scope::All);
+    }
+
+    match apikey {
+        Some(key) if is_valid_apikey(key) => Ok(Scope::Owner(apikey_owner(key))),
+        Some(_) => Err(ApiError::unauthorized("invalid_apikey", "Invalid API key")),
+        None => session_requester(req, state)
+            .map(Scope::Owner)
+            .ok_or_else(|| ApiError::unauthorized("apikey_required", "An API key or admin key is required to list tasks")),
+    }
+}
+
+fn can_view(task: &Task, req: &HttpRequest, state: &AppState, apikey: Option<&str>) -> bool {
+    if task.owner.is_empty() || is_admin_request(req) {
+        return true;
+    }
+    match apikey {
+        Some(key) => is_valid_apikey(key) && apikey_owner(key) == task.owner,
+        None => session_requester(req, state).is_some_and(|owner| owner == task.owner),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}",
+    tag = "tasks",
+    params(("id" = String, Path, description = "Task id"), TaskLookupQuery),
+    responses(
+        (status = 200, description = "Task", body = Task),
+        (status = 404, description = "Task not found", body = ApiError),
+    )
+)]
+pub async fn get_task(
+    path: web::Path<String>,
+    query: web::Query<TaskLookupQuery>,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let task_id = path.into_inner();
+
+    match state.tasks.get(&task_id) {
+        Some(task) if can_view(&task, &req, &state, query.apikey.as_deref()) => Ok(HttpResponse::Ok().json(task.clone())),
+        _ => Err(ApiError::not_found("task_not_found", "Task not found")),
+    }
+}
+
+fn can_view_logs(task: &Task, req: &HttpRequest, apikey: Option<&str>) -> bool {
+    if is_admin_request(req) {
+        return true;
+    }
+    !task.owner.is_empty() && apikey.is_some_and(|key| is_valid_apikey(key) && apikey_owner(key) == task.owner)
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks/{id}/logs",
+    tag = "tasks",
+    params(("id" = String, Path, description = "Task id"), TaskLookupQuery),
+    responses(
+        (status = 200, description = "Captured yt-dlp output per format attempt, oldest first", body = TaskLogResponse),
+        (status = 404, description = "Task not found", body = ApiError),
+    )
+)]
+pub async fn get_task_logs(
+    path: web::Path<String>,
+    query: web::Query<TaskLookupQuery>,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let task_id = path.into_inner();
+
+    let visible = state.tasks.get(&task_id).is_some_and(|task| can_view_logs(&task, &req, query.apikey.as_deref()));
+    if !visible {
+        return Err(ApiError::not_found("task_not_found", "Task not found"));
+    }
+
+    let entries = state.task_logs.get(&task_id).map(|entries| entries.clone()).unwrap_or_default();
+    Ok(HttpResponse::Ok().json(TaskLogResponse { task_id, entries }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/v1/tasks",
+    tag = "tasks",
+    params(TaskListQuery),
+    responses(
+        (status = 200, description = "Tasks visible to the caller, newest first", body = TaskListResponse),
+        (status = 401, description = "Missing or invalid API key", body = ApiError),
+    )
+)]
+pub async fn list_tasks(
+    query: web::Query<TaskListQuery>,
+    state: web::Data<AppState>,
+    req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+    let query = query.into_inner();
+    let owner = match requester_scope(&req, &state, query.apikey.as_deref())? {
+        Scope::All => query.owner.as_deref().map(apikey_owner),
+        Scope::Owner(owner) => Some(owner),
+    };
+
+    let mut tasks: Vec<Task> = state.tasks.iter()
+        .filter(|task| owner.as_ref().is_none_or(|owner| &task.owner == owner))
+        .filter(|task| query.status.as_ref().is_none_or(|status| &task.status == status))
+        .filter(|task| query.media_type.as_ref().is_none_or(|media_type| &task.media_type == media_type))
+        .filter(|task| query.created_after.is_none_or(|after| task.created_at >= after))
+        .filter(|task| query.created_before.is_none_or(|before| task.created_at < before))
+        .map(|task| task.clone())
+        .collect();
+    tasks.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| a.id.cmp(&b.id)));
+
+    let total = tasks.len();
+    let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    let tasks = tasks.into_iter().skip(offset).take(limit).collect();
+
+    Ok(HttpResponse::Ok().json(TaskListResponse { tasks, total, limit, offset }))
+}
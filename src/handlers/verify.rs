@@ -1,14 +1,25 @@
-use actix_web::{web, HttpRequest, HttpResponse, Result, cookie::Cookie};
+use actix_web::{web, HttpRequest, HttpResponse, cookie::Cookie};
 use chrono::Utc;
-use crate::models::{VerifyRequest, PowSession, AppState};
-use crate::services::youtube::extract_video_id;
+use crate::models::{VerifyRequest, PowSession, AppState, ApiError};
+use ytdl_core::extract_video_id;
+use crate::services::content_key;
 use crate::utils::{get_real_ip, get_client_identifier, verify_pow, generate_session_token};
 
+#[utoipa::path(
+    post,
+    path = "/v1/verify",
+    tag = "auth",
+    request_body = VerifyRequest,
+    responses(
+        (status = 200, description = "PoW verified; sets the pow_session cookie"),
+        (status = 400, description = "Challenge missing, expired or invalid", body = ApiError),
+    )
+)]
 pub async fn cekpunyaku(
     body: web::Json<VerifyRequest>,
     state: web::Data<AppState>,
     req: HttpRequest,
-) -> Result<HttpResponse> {
+) -> Result<HttpResponse, ApiError> {
     let ip = get_real_ip(&req);
     
     let user_agent = req.headers()
@@ -21,33 +32,23 @@ pub async fn cekpunyaku(
     
     let challenge = match state.pow_challenges.get(&client_id) {
         Some(c) => c.clone(),
-        None => {
-            return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-                "error": "Challenge not found or expired"
-            })));
-        }
+        None => return Err(ApiError::bad_request("challenge_not_found", "Challenge not found or expired")),
     };
     
     if !verify_pow(&challenge.challenge, &body.nonce, challenge.difficulty) {
-        return Ok(HttpResponse::BadRequest().json(serde_json::json!({
-            "error": "Invalid proof of work"
-        })));
+        return Err(ApiError::bad_request("invalid_pow", "Invalid proof of work"));
     }
     
-    let video_id = match extract_video_id(&body.url) {
-        Ok(id) => id,
-        Err(_) => {
-            return Ok(HttpResponse::BadRequest().json(serde_json::json!({"error": "Invalid YouTube URL"})));
-        }
-    };
+    let video_id = extract_video_id(&body.url)
+        .map_err(|_| ApiError::bad_request("invalid_url", "Invalid YouTube URL"))?;
     
-    let task_id = format!("{}_{}", video_id, body.r#type);
-    let session_token = generate_session_token(&task_id, &ip, &user_agent);
+    let content_key = content_key(&video_id, &body.r#type);
+    let session_token = generate_session_token(&content_key, &ip, &user_agent);
     
     state.pow_sessions.insert(
         session_token.clone(),
         PowSession {
-            task_id: task_id.clone(),
+            content_key,
             ip: ip.clone(),
             user_agent: user_agent.clone(),
             timestamp: Utc::now().timestamp_millis(),
@@ -55,6 +56,7 @@ pub async fn cekpunyaku(
     );
     
     state.pow_challenges.remove(&client_id);
+    state.metrics.pow_verified.inc();
     
     let cookie = Cookie::build("pow_session", session_token)
         .path("/")
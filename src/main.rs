@@ -1,5 +1,7 @@
 use actix_web::{web, App, HttpServer};
+use actix_web::middleware::from_fn;
 
+mod cli;
 mod config;
 mod models;
 mod utils;
@@ -7,15 +9,30 @@ mod services;
 mod middleware;
 mod handlers;
 
-use config::{get_downloads_dir, get_audio_dir, get_video_dir, get_merge_dir, get_cookies_dir, get_port, get_max_concurrent};
-use models::AppState;
-use utils::{log_startup, find_executable};
-use services::cleanup_cache;
-use handlers::{akumaudownload, cekpunyaku, status, download, serve_file};
-use middleware::RequestLogger;
+use config::{watch_config, get_downloads_dir, get_audio_dir, get_video_dir, get_merge_dir, get_cookies_dir, get_port, get_max_concurrent};
+use models::{AppState, ApiError};
+use utils::{init_logging, log_startup, find_executable};
+use services::{scan_media_dirs, update_disk_usage, cleanup_cache, probe_proxies, resume_webhooks, handle_shutdown, resume_pending_tasks};
+use handlers::{akumaudownload, cekpunyaku, status, download, create_download, get_task, get_task_logs, list_tasks, serve_file, openapi_json, list_cookies, upload_cookies, download_cookies, delete_cookies, get_config, metrics};
+use middleware::{RequestLogger, legacy_json_errors, legacy_text_errors};
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
+    if let Err(e) = config::init() {
+        eprintln!("Invalid configuration: {}", e);
+        std::process::exit(1);
+    }
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.first().is_some_and(|arg| arg == "fetch") {
+        std::process::exit(cli::run(&args).await);
+    }
+
+    init_logging();
+    if !args.is_empty() {
+        tracing::warn!(?args, "ignoring command-line arguments; use `youtube-downloader fetch` to download from the command line");
+    }
+
     let downloads_dir = get_downloads_dir();
     let audio_dir = get_audio_dir();
     let video_dir = get_video_dir();
@@ -26,25 +43,37 @@ async fn main() -> std::io::Result<()> {
         tokio::fs::create_dir_all(dir).await?;
     }
 
-    let cpu_cores = num_cpus::get();
-    let max_concurrent = get_max_concurrent().unwrap_or_else(|| (cpu_cores * 2).max(4).min(32));
+    let max_concurrent = get_max_concurrent();
 
     let ffmpeg_path = find_executable("ffmpeg");
     let ffprobe_path = find_executable("ffprobe");
 
     let state = web::Data::new(AppState::new(max_concurrent, ffmpeg_path, ffprobe_path));
 
+    scan_media_dirs(&state).await;
+    update_disk_usage(&state).await;
+
     let state_clone = state.clone();
     tokio::spawn(async move {
         cleanup_cache(state_clone).await;
     });
 
+    let state_clone = state.clone();
+    tokio::spawn(async move {
+        probe_proxies(state_clone).await;
+    });
+
+    tokio::spawn(watch_config());
+    tokio::spawn(resume_webhooks(state.clone()));
+    tokio::spawn(resume_pending_tasks(state.clone()));
+
     let port = get_port();
     let bind_addr = format!("0.0.0.0:{}", port);
     
     log_startup(&format!("YouTube Downloader v2.0.0 - Listening on {}", bind_addr));
 
-    HttpServer::new(move || {
+    let app_state = state.clone();
+    let server = HttpServer::new(move || {
         App::new()
             .wrap(RequestLogger)
             .wrap(
@@ -54,14 +83,45 @@ async fn main() -> std::io::Result<()> {
                     .allow_any_header()
                     .supports_credentials()
             )
-            .app_data(state.clone())
+            .app_data(app_state.clone())
+            .app_data(web::JsonConfig::default().error_handler(|err, _| {
+                ApiError::bad_request("invalid_body", &err.to_string()).into()
+            }))
+            .app_data(web::QueryConfig::default().error_handler(|err, _| {
+                ApiError::bad_request("invalid_query", &err.to_string()).into()
+            }))
+            .service(
+                web::scope("/v1")
+                    .route("/openapi.json", web::get().to(openapi_json))
+                    .route("/status", web::get().to(status))
+                    .route("/challenge", web::post().to(akumaudownload))
+                    .route("/verify", web::post().to(cekpunyaku))
+                    .route("/downloads", web::post().to(create_download))
+                    .route("/tasks", web::get().to(list_tasks))
+                    .route("/tasks/{id}", web::get().to(get_task))
+                    .route("/tasks/{id}/logs", web::get().to(get_task_logs))
+                    .route("/files/{filename}", web::get().to(serve_file))
+            )
             .route("/", web::get().to(status))
-            .route("/akumaudownload", web::post().to(akumaudownload))
-            .route("/cekpunyaku", web::post().to(cekpunyaku))
-            .route("/download", web::get().to(download))
-            .route("/files/{filename}", web::get().to(serve_file))
+            .route("/metrics", web::get().to(metrics))
+            .service(web::resource("/akumaudownload").wrap(from_fn(legacy_json_errors)).route(web::post().to(akumaudownload)))
+            .service(web::resource("/cekpunyaku").wrap(from_fn(legacy_json_errors)).route(web::post().to(cekpunyaku)))
+            .service(web::resource("/download").wrap(from_fn(legacy_json_errors)).route(web::get().to(download)))
+            .service(web::resource("/files/{filename}").wrap(from_fn(legacy_text_errors)).route(web::get().to(serve_file)))
+            .route("/tasks", web::get().to(list_tasks))
+            .route("/tasks/{id}", web::get().to(get_task))
+            .route("/tasks/{id}/logs", web::get().to(get_task_logs))
+            .route("/admin/config", web::get().to(get_config))
+            .route("/admin/cookies", web::get().to(list_cookies))
+            .route("/admin/cookies/{name}", web::put().to(upload_cookies))
+            .route("/admin/cookies/{name}", web::get().to(download_cookies))
+            .route("/admin/cookies/{name}", web::delete().to(delete_cookies))
     })
     .bind(&bind_addr)?
-    .run()
-    .await
+    .disable_signals()
+    .run();
+
+    tokio::spawn(handle_shutdown(state, server.handle()));
+
+    server.await
 }
@@ -1,6 +1,24 @@
-use crate::config::get_valid_apikeys;
+use actix_web::HttpRequest;
+use sha2::{Digest, Sha256};
+use crate::config::{get_valid_apikeys, get_admin_keys};
 
 pub fn is_valid_apikey(apikey: &str) -> bool {
     let valid_keys = get_valid_apikeys();
     valid_keys.iter().any(|key| key == apikey)
 }
+
+pub fn is_admin_request(req: &HttpRequest) -> bool {
+    let admin_key = match req.headers().get("x-admin-key").and_then(|v| v.to_str().ok()) {
+        Some(key) if !key.is_empty() => key,
+        _ => return false,
+    };
+
+    // Compare fixed-length digests without short-circuiting so response timing
+    // does not reveal how much of a key matched.
+    let presented = Sha256::digest(admin_key.as_bytes());
+    get_admin_keys().iter().fold(false, |matched, key| {
+        let expected = Sha256::digest(key.as_bytes());
+        let diff = presented.iter().zip(expected.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b));
+        matched | (diff == 0)
+    })
+}
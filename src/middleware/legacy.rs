@@ -0,0 +1,25 @@
+use actix_web::body::BoxBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{Error, HttpResponse};
+use crate::models::{ApiError, ErrorLayout};
+
+async fn relayout_errors(req: ServiceRequest, next: Next<BoxBody>, layout: ErrorLayout) -> Result<ServiceResponse<BoxBody>, Error> {
+    let res = next.call(req).await?;
+    let error = res.response().error()
+        .and_then(|error| error.as_error::<ApiError>())
+        .map(|error| error.clone().with_layout(layout));
+
+    Ok(match error {
+        Some(error) => res.into_response(HttpResponse::from_error(error)),
+        None => res,
+    })
+}
+
+pub async fn legacy_json_errors(req: ServiceRequest, next: Next<BoxBody>) -> Result<ServiceResponse<BoxBody>, Error> {
+    relayout_errors(req, next, ErrorLayout::Legacy).await
+}
+
+pub async fn legacy_text_errors(req: ServiceRequest, next: Next<BoxBody>) -> Result<ServiceResponse<BoxBody>, Error> {
+    relayout_errors(req, next, ErrorLayout::PlainText).await
+}
@@ -1,14 +1,21 @@
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
-    Error,
+    http::header::{HeaderName, HeaderValue},
+    web, Error, HttpMessage,
 };
 use futures::future::LocalBoxFuture;
 use std::future::{ready, Ready};
 use std::time::Instant;
+use tracing::Instrument;
+use uuid::Uuid;
+use crate::models::AppState;
 use crate::utils::logger::log_request;
 
 pub struct RequestLogger;
 
+#[derive(Clone)]
+pub struct RequestId(pub String);
+
 impl<S, B> Transform<S, ServiceRequest> for RequestLogger
 where
     S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
@@ -47,14 +54,36 @@ where
         let method = req.method().to_string();
         let path = req.path().to_string();
 
-        let fut = self.service.call(req);
+        let request_id = req.headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .filter(|v| !v.is_empty() && v.len() <= 128)
+            .map(String::from)
+            .unwrap_or_else(|| Uuid::new_v4().to_string());
+        req.extensions_mut().insert(RequestId(request_id.clone()));
+
+        let span = tracing::info_span!("request", request_id = %request_id);
+        let fut = self.service.call(req).instrument(span);
 
         Box::pin(async move {
-            let res = fut.await?;
-            let latency = start.elapsed().as_millis();
+            let mut res = fut.await?;
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                res.headers_mut().insert(HeaderName::from_static("x-request-id"), value);
+            }
+
+            let elapsed = start.elapsed();
+            let latency = elapsed.as_millis();
             let status = res.status().as_u16();
             
-            log_request(&method, &path, status, latency);
+            log_request(&request_id, &method, &path, status, latency);
+
+            if let Some(state) = res.request().app_data::<web::Data<AppState>>() {
+                let route = res.request().match_pattern().unwrap_or_else(|| "unmatched".to_string());
+                let status_label = status.to_string();
+                let labels = [route.as_str(), method.as_str(), status_label.as_str()];
+                state.metrics.http_requests.with_label_values(&labels).inc();
+                state.metrics.http_request_duration.with_label_values(&labels).observe(elapsed.as_secs_f64());
+            }
             
             Ok(res)
         })
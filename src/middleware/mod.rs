@@ -1,5 +1,7 @@
 pub mod auth;
 pub mod logger;
+pub mod legacy;
 
 pub use auth::*;
 pub use logger::*;
+pub use legacy::*;
@@ -0,0 +1,146 @@
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use serde::Serialize;
+use std::fmt;
+use utoipa::ToSchema;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorLayout {
+    #[default]
+    Envelope,
+    Legacy,
+    PlainText,
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: String,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Box<serde_json::Value>>,
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
+    #[serde(skip)]
+    pub layout: ErrorLayout,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, error: &str) -> Self {
+        Self {
+            status,
+            error: error.to_string(),
+            code,
+            action: None,
+            details: None,
+            retry_after: None,
+            layout: ErrorLayout::Envelope,
+        }
+    }
+
+    pub fn bad_request(code: &'static str, error: &str) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, code, error)
+    }
+
+    pub fn unauthorized(code: &'static str, error: &str) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, code, error)
+    }
+
+    pub fn forbidden(code: &'static str, error: &str) -> Self {
+        Self::new(StatusCode::FORBIDDEN, code, error)
+    }
+
+    pub fn not_found(code: &'static str, error: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, code, error)
+    }
+
+    pub fn unavailable(code: &'static str, error: &str, retry_after: u64) -> Self {
+        let mut err = Self::new(StatusCode::SERVICE_UNAVAILABLE, code, error);
+        err.retry_after = Some(retry_after);
+        err
+    }
+
+    pub fn too_many_requests(code: &'static str, error: &str, retry_after: u64) -> Self {
+        let mut err = Self::new(StatusCode::TOO_MANY_REQUESTS, code, error);
+        err.retry_after = Some(retry_after);
+        err
+    }
+
+    pub fn internal(error: &str) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", error)
+    }
+
+    pub fn pow_required() -> Self {
+        Self::unauthorized("pow_required", "PoW challenge required").with_action("get_challenge")
+    }
+
+    pub fn with_action(mut self, action: &str) -> Self {
+        self.action = Some(action.to_string());
+        self
+    }
+
+    pub fn with_details(mut self, details: serde_json::Value) -> Self {
+        self.details = Some(Box::new(details));
+        self
+    }
+
+    pub fn with_layout(mut self, layout: ErrorLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    fn legacy_body(&self) -> serde_json::Value {
+        let mut body = serde_json::Map::new();
+        body.insert("error".to_string(), self.error.clone().into());
+        body.insert("code".to_string(), self.code.into());
+        if let Some(action) = &self.action {
+            body.insert("action".to_string(), action.clone().into());
+        }
+        match self.details.as_deref() {
+            Some(serde_json::Value::Object(details)) => {
+                for (key, value) in details {
+                    body.entry(key.clone()).or_insert_with(|| value.clone());
+                }
+            }
+            Some(details) => {
+                body.insert("details".to_string(), details.clone());
+            }
+            None => (),
+        }
+        serde_json::Value::Object(body)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.error)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        if let Some(retry_after) = self.retry_after {
+            response.insert_header(("Retry-After", retry_after.to_string()));
+        }
+        match self.layout {
+            ErrorLayout::Envelope => response.json(self),
+            ErrorLayout::Legacy => response.json(self.legacy_body()),
+            ErrorLayout::PlainText => response.content_type("text/plain; charset=utf-8").body(self.error.clone()),
+        }
+    }
+}
+
+impl From<std::io::Error> for ApiError {
+    fn from(err: std::io::Error) -> Self {
+        tracing::error!(error = %err, "I/O error while handling request");
+        Self::internal("Internal I/O error")
+    }
+}
@@ -1,16 +1,31 @@
 use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use ytdl_core::MediaInfo;
 
 pub mod state;
+pub mod error;
 pub use state::AppState;
+pub use error::{ApiError, ErrorLayout};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct CacheEntry {
     pub file_path: String,
     pub timestamp: i64,
     pub media_type: String,
+    #[serde(default)]
+    pub media_info: Option<MediaInfo>,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
+pub struct CacheSidecar {
+    pub video_id: String,
+    pub media_type: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub media_info: Option<MediaInfo>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize, ToSchema)]
 pub struct Task {
     pub id: String,
     pub url: String,
@@ -21,6 +36,41 @@ pub struct Task {
     pub file_url: String,
     pub created_at: i64,
     pub error: String,
+    #[serde(default)]
+    pub error_code: String,
+    #[serde(default)]
+    pub finished_at: i64,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub owner: String,
+    #[serde(skip)]
+    pub content_key: String,
+    #[serde(skip)]
+    pub priority: i64,
+    #[serde(skip)]
+    pub client: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub media_info: Option<MediaInfo>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PendingTask {
+    pub task: Task,
+    pub callbacks: Vec<String>,
+    #[serde(default)]
+    pub priority: i64,
+    #[serde(default)]
+    pub client: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WebhookEvent {
+    pub id: String,
+    pub url: String,
+    pub task: Task,
+    pub attempts: u32,
+    pub created_at: i64,
+    pub last_error: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -34,47 +84,133 @@ pub struct PowChallenge {
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PowSession {
-    pub task_id: String,
+    pub content_key: String,
     pub ip: String,
     pub user_agent: String,
     pub timestamp: i64,
 }
 
-#[derive(Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CookieHealth {
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub last_success: i64,
+    pub last_failure: i64,
+    pub last_error: String,
+    pub quarantined_until: i64,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ProxyHealth {
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub down: bool,
+    pub down_since: i64,
+    pub last_error: String,
+}
+
+#[derive(Deserialize, ToSchema, IntoParams)]
 pub struct DownloadQuery {
     pub url: String,
     pub r#type: String,
     pub apikey: Option<String>,
+    pub callback_url: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TaskLookupQuery {
+    pub apikey: Option<String>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TaskListQuery {
+    pub apikey: Option<String>,
+    pub status: Option<String>,
+    pub media_type: Option<String>,
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+    pub owner: Option<String>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct TaskLogEntry {
+    pub timestamp: i64,
+    pub attempt: usize,
+    pub format: String,
+    pub error_code: String,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TaskLogResponse {
+    pub task_id: String,
+    pub entries: Vec<TaskLogEntry>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TaskListResponse {
+    pub tasks: Vec<Task>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct ChallengeRequest {
     pub url: String,
     pub r#type: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct VerifyRequest {
     pub url: String,
     pub r#type: String,
     pub nonce: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ChallengeResponse {
     pub challenge: String,
     pub difficulty: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+#[serde(untagged)]
+pub enum DownloadResponse {
+    Task(Box<Task>),
+    Cached(Box<CachedDownloadResponse>),
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CachedDownloadResponse {
+    pub status: String,
+    #[serde(rename = "fileUrl")]
+    pub file_url: String,
+    pub cached: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub media_info: Option<MediaInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct StatusResponse {
     pub status: String,
     pub system: SystemInfo,
     pub cache: CacheInfo,
+    pub disk: DiskInfo,
+    pub scheduler: SchedulerInfo,
+    pub load: LoadInfo,
     pub tasks: TaskInfo,
+    pub cookies: CookieInfo,
+    pub proxies: ProxyInfo,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SystemInfo {
     pub uptime: String,
     pub version: String,
@@ -86,16 +222,95 @@ pub struct SystemInfo {
     pub active_cookies: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CacheInfo {
     pub total: usize,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
+pub struct DiskInfo {
+    pub margin: u64,
+    pub dirs: Vec<DiskUsage>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DiskUsage {
+    pub media_type: String,
+    pub dir: String,
+    pub free_bytes: Option<u64>,
+    pub total_bytes: Option<u64>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoadInfo {
+    pub shedding: bool,
+    pub reasons: Vec<String>,
+    pub queued: usize,
+    pub cpu_load: Option<f64>,
+    pub active_processes: usize,
+    pub max_queue_length: usize,
+    pub max_cpu_load: f64,
+    pub max_active_processes: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SchedulerInfo {
+    pub workers: usize,
+    pub reserved_premium: usize,
+    pub running: usize,
+    pub queued: usize,
+}
+
+#[derive(Serialize, ToSchema)]
 pub struct TaskInfo {
     pub total: usize,
+    pub queued: usize,
     pub downloading: usize,
     pub processing: usize,
     pub completed: usize,
     pub failed: usize,
 }
+
+#[derive(Serialize, ToSchema)]
+pub struct CookieInfo {
+    pub total: usize,
+    pub healthy: usize,
+    pub quarantined: usize,
+    pub warnings: Vec<String>,
+    pub files: Vec<CookieFileInfo>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CookieFileInfo {
+    pub file: String,
+    pub valid: bool,
+    pub login_cookies: Vec<String>,
+    pub expires_at: Option<i64>,
+    pub expiring_soon: bool,
+    pub healthy: bool,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub last_error: String,
+    pub quarantined_for: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProxyInfo {
+    pub strategy: String,
+    pub total: usize,
+    pub up: usize,
+    pub down: usize,
+    pub entries: Vec<ProxyStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProxyStatus {
+    pub proxy: String,
+    pub up: bool,
+    pub successes: u64,
+    pub failures: u64,
+    pub consecutive_failures: u32,
+    pub last_error: String,
+    pub down_for: i64,
+}
@@ -1,12 +1,31 @@
 use dashmap::DashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize};
 use std::time::Instant;
-use crate::models::{CacheEntry, Task, PowChallenge, PowSession};
+use crate::services::cookies::CachedCookieJar;
+use crate::services::metrics::Metrics;
+use crate::services::scheduler::Scheduler;
+use crate::models::{CacheEntry, Task, TaskLogEntry, PowChallenge, PowSession, CookieHealth, ProxyHealth};
 
 pub struct AppState {
     pub cache: DashMap<String, CacheEntry>,
     pub tasks: DashMap<String, Task>,
+    pub jobs: DashMap<String, Vec<String>>,
+    pub task_logs: DashMap<String, Vec<TaskLogEntry>>,
     pub pow_challenges: DashMap<String, PowChallenge>,
     pub pow_sessions: DashMap<String, PowSession>,
+    pub cookie_health: DashMap<String, CookieHealth>,
+    pub cookie_summaries: DashMap<String, CachedCookieJar>,
+    pub proxy_health: DashMap<String, ProxyHealth>,
+    pub proxy_cursor: AtomicUsize,
+    pub task_callbacks: DashMap<String, Vec<String>>,
+    pub http_client: reqwest::Client,
+    pub shutting_down: AtomicBool,
+    pub active_jobs: AtomicUsize,
+    pub webhook_deliveries: AtomicUsize,
+    pub scheduler: Scheduler,
+    pub task_admission: Mutex<()>,
+    pub metrics: Metrics,
     pub start_time: Instant,
     pub max_concurrent: usize,
     pub ffmpeg_path: String,
@@ -18,8 +37,25 @@ impl AppState {
         Self {
             cache: DashMap::new(),
             tasks: DashMap::new(),
+            jobs: DashMap::new(),
+            task_logs: DashMap::new(),
             pow_challenges: DashMap::new(),
             pow_sessions: DashMap::new(),
+            cookie_health: DashMap::new(),
+            cookie_summaries: DashMap::new(),
+            proxy_health: DashMap::new(),
+            proxy_cursor: AtomicUsize::new(0),
+            task_callbacks: DashMap::new(),
+            http_client: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .unwrap_or_default(),
+            shutting_down: AtomicBool::new(false),
+            active_jobs: AtomicUsize::new(0),
+            webhook_deliveries: AtomicUsize::new(0),
+            scheduler: Scheduler::new(max_concurrent),
+            task_admission: Mutex::new(()),
+            metrics: Metrics::new(),
             start_time: Instant::now(),
             max_concurrent,
             ffmpeg_path,
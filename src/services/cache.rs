@@ -1,31 +1,253 @@
 use actix_web::web;
-use tokio::time::{sleep, Duration};
+use tokio::fs;
+use tokio::time::{sleep, Duration, Instant};
 use chrono::Utc;
-use crate::models::AppState;
-use crate::config::get_cache_duration;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+use ytdl_core::device_id;
+use crate::models::{AppState, CacheEntry, CacheSidecar};
+use crate::services::download::content_key;
+use crate::utils::dir_size;
+use crate::config::{get_cache_duration, get_completed_task_retention, get_failed_task_retention, get_orphan_grace, get_audio_dir, get_video_dir, get_merge_dir};
+
+pub fn sidecar_path(file_path: &str) -> String {
+    format!("{}.json", file_path)
+}
+
+pub async fn write_sidecar(file_path: &str, sidecar: &CacheSidecar) {
+    let path = sidecar_path(file_path);
+    let result = match serde_json::to_vec(sidecar) {
+        Ok(json) => fs::write(&path, json).await,
+        Err(e) => Err(std::io::Error::other(e)),
+    };
+    if let Err(e) = result {
+        tracing::warn!(path = %path, error = %e, "failed to write cache sidecar");
+    }
+}
+
+async fn read_sidecar(file_path: &str) -> Option<CacheSidecar> {
+    let bytes = fs::read(sidecar_path(file_path)).await.ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+async fn remove_with_sidecar(file_path: &str) {
+    let _ = fs::remove_file(file_path).await;
+    let _ = fs::remove_file(sidecar_path(file_path)).await;
+}
+
+fn age_secs(metadata: &std::fs::Metadata) -> u64 {
+    metadata.modified()
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age.as_secs())
+        .unwrap_or(0)
+}
+
+#[derive(Default)]
+struct ScanReport {
+    registered: usize,
+    expired: usize,
+    orphans: usize,
+    reclaimed_bytes: u64,
+}
+
+async fn scan_dir(state: &AppState, media_type: &str, dir: &str, cached: &HashSet<String>, report: &mut ScanReport) {
+    let mut entries = match fs::read_dir(dir).await {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let now = Utc::now().timestamp_millis();
+    let cache_duration = get_cache_duration();
+    let grace = get_orphan_grace();
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let metadata = match entry.metadata().await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => continue,
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        let file_path = format!("{}/{}", dir, name);
+
+        if let Some(media_path) = file_path.strip_suffix(".json") {
+            if !Path::new(media_path).exists() && age_secs(&metadata) >= grace {
+                let _ = fs::remove_file(&file_path).await;
+                report.orphans += 1;
+                report.reclaimed_bytes += metadata.len();
+            }
+            continue;
+        }
+
+        if cached.contains(&file_path) {
+            continue;
+        }
+
+        match read_sidecar(&file_path).await {
+            Some(sidecar) if sidecar.media_type == media_type => {
+                if now - sidecar.timestamp > cache_duration {
+                    remove_with_sidecar(&file_path).await;
+                    state.metrics.cache_evictions.inc();
+                    report.expired += 1;
+                    report.reclaimed_bytes += metadata.len();
+                    continue;
+                }
+
+                let cache_key = format!("{:x}", md5::compute(content_key(&sidecar.video_id, &sidecar.media_type)));
+                let existing = state.cache.get(&cache_key).map(|existing| existing.file_path.clone());
+                if existing.as_deref() == Some(file_path.as_str()) {
+                    continue;
+                }
+                if existing.is_some_and(|existing| Path::new(&existing).exists()) {
+                    if age_secs(&metadata) >= grace {
+                        remove_with_sidecar(&file_path).await;
+                        report.orphans += 1;
+                        report.reclaimed_bytes += metadata.len();
+                    }
+                    continue;
+                }
+
+                state.cache.insert(cache_key, CacheEntry {
+                    file_path,
+                    timestamp: sidecar.timestamp,
+                    media_type: sidecar.media_type,
+                    media_info: sidecar.media_info,
+                });
+                report.registered += 1;
+            }
+            _ if age_secs(&metadata) >= grace => {
+                remove_with_sidecar(&file_path).await;
+                report.orphans += 1;
+                report.reclaimed_bytes += metadata.len();
+            }
+            _ => {}
+        }
+    }
+}
+
+pub async fn scan_media_dirs(state: &AppState) {
+    let cached: HashSet<String> = state.cache.iter().map(|entry| entry.file_path.clone()).collect();
+    let mut report = ScanReport::default();
+
+    for (media_type, dir) in [("audio", get_audio_dir()), ("video", get_video_dir()), ("merge", get_merge_dir())] {
+        scan_dir(state, media_type, &dir, &cached, &mut report).await;
+    }
+
+    tracing::info!(
+        registered = report.registered,
+        expired = report.expired,
+        orphans_removed = report.orphans,
+        reclaimed_bytes = report.reclaimed_bytes,
+        "media directory scan finished"
+    );
+}
+
+pub fn evict_for_space(state: &AppState, dir: &Path, bytes: u64, keep: Option<&Path>) -> u64 {
+    // Only files on the filesystem that is short on space are worth evicting.
+    let device = device_id(dir);
+    let mut candidates: Vec<(String, i64, String, u64)> = state.cache.iter()
+        .filter(|entry| keep.is_none_or(|keep| Path::new(&entry.file_path) != keep))
+        .filter_map(|entry| {
+            let path = Path::new(&entry.file_path);
+            if device.is_some() && device_id(path) != device {
+                return None;
+            }
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            Some((entry.key().clone(), entry.timestamp, entry.file_path.clone(), size))
+        })
+        .collect();
+
+    let evictable: u64 = candidates.iter().map(|(_, _, _, size)| size).sum();
+    if evictable < bytes {
+        tracing::warn!(requested_bytes = bytes, evictable_bytes = evictable, "evicting the cache would not free enough disk space");
+        return 0;
+    }
+    candidates.sort_by_key(|(_, timestamp, _, _)| *timestamp);
+
+    let mut reclaimed = 0;
+    let mut evicted = 0;
+    for (cache_key, _, file_path, size) in candidates {
+        if reclaimed >= bytes {
+            break;
+        }
+        state.cache.remove(&cache_key);
+        let _ = std::fs::remove_file(&file_path);
+        let _ = std::fs::remove_file(sidecar_path(&file_path));
+        state.metrics.cache_evictions.inc();
+        reclaimed += size;
+        evicted += 1;
+    }
+
+    tracing::warn!(requested_bytes = bytes, reclaimed_bytes = reclaimed, evicted, "evicted cached files to free disk space");
+    reclaimed
+}
+
+/// Refreshes the disk usage gauges; done by the sweeper so that scraping
+/// `/metrics` never walks the media directories.
+pub async fn update_disk_usage(state: &AppState) {
+    for (media_type, dir) in [("audio", get_audio_dir()), ("video", get_video_dir()), ("merge", get_merge_dir())] {
+        state.metrics.disk_usage.with_label_values(&[media_type]).set(dir_size(&dir).await as i64);
+    }
+}
+
+pub fn sweep_tasks(state: &AppState) {
+    let now = Utc::now().timestamp_millis();
+    let completed_retention = get_completed_task_retention() * 1000;
+    let failed_retention = get_failed_task_retention() * 1000;
+    let before = state.tasks.len();
+
+    state.tasks.retain(|_, task| {
+        let retention = match task.status.as_str() {
+            "completed" => completed_retention,
+            "failed" => failed_retention,
+            _ => return true,
+        };
+        now - task.finished_at <= retention
+    });
+
+    state.task_logs.retain(|task_id, _| state.tasks.contains_key(task_id));
+
+    let removed = before.saturating_sub(state.tasks.len());
+    if removed > 0 {
+        tracing::debug!(removed, remaining = state.tasks.len(), "expired finished tasks");
+    }
+}
 
 pub async fn cleanup_cache(state: web::Data<AppState>) {
+    let mut last_cache_sweep = Instant::now();
+
     loop {
-        sleep(Duration::from_secs(3600)).await;
-        
+        sleep(Duration::from_secs(60)).await;
+
         let now = Utc::now().timestamp_millis();
+        sweep_tasks(&state);
+        update_disk_usage(&state).await;
+
+        state.pow_challenges.retain(|_, challenge| {
+            now - challenge.timestamp < 300000
+        });
+
+        state.pow_sessions.retain(|_, session| {
+            now - session.timestamp < 3600000
+        });
+
+        if last_cache_sweep.elapsed() < Duration::from_secs(3600) {
+            continue;
+        }
+        last_cache_sweep = Instant::now();
+
         let cache_duration = get_cache_duration();
-        
         state.cache.retain(|_, entry| {
             if now - entry.timestamp > cache_duration {
                 let _ = std::fs::remove_file(&entry.file_path);
+                let _ = std::fs::remove_file(sidecar_path(&entry.file_path));
+                state.metrics.cache_evictions.inc();
                 false
             } else {
                 true
             }
         });
-        
-        state.pow_challenges.retain(|_, challenge| {
-            now - challenge.timestamp < 300000
-        });
-        
-        state.pow_sessions.retain(|_, session| {
-            now - session.timestamp < 3600000
-        });
+
+        scan_media_dirs(&state).await;
     }
 }
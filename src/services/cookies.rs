@@ -0,0 +1,256 @@
+use rand::seq::SliceRandom;
+use chrono::Utc;
+use std::path::Path;
+use std::time::SystemTime;
+use crate::models::{AppState, CookieFileInfo};
+use ytdl_core::YtdlpError;
+use crate::config::{get_cookie_quarantine_base, get_cookie_quarantine_max, get_cookie_expiry_warning};
+use crate::utils::helpers::get_available_cookies;
+
+const LOGIN_COOKIES: &[&str] = &[
+    "SID", "HSID", "SSID", "APISID", "SAPISID",
+    "__Secure-1PSID", "__Secure-3PSID", "LOGIN_INFO",
+];
+
+#[derive(Clone)]
+pub struct CookieJarSummary {
+    pub cookies: usize,
+    pub login_cookies: Vec<String>,
+    pub expires_at: Option<i64>,
+}
+
+fn is_domain_of(domain: &str, site: &str) -> bool {
+    let domain = domain.strip_prefix('.').unwrap_or(domain);
+    domain == site || domain.strip_suffix(site).is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+pub fn parse_netscape_cookies(content: &str) -> Result<CookieJarSummary, String> {
+    let mut cookies = 0;
+    let mut login_cookies: Vec<String> = Vec::new();
+    let mut expires_at: Option<i64> = None;
+
+    for (index, raw) in content.lines().enumerate() {
+        let line = raw.trim_end_matches('\r');
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(format!("Line {}: expected 7 tab-separated fields, found {}", index + 1, fields.len()));
+        }
+
+        let (domain, subdomains, secure, expiry, name) = (fields[0], fields[1], fields[3], fields[4], fields[5]);
+
+        for flag in [subdomains, secure] {
+            if flag != "TRUE" && flag != "FALSE" {
+                return Err(format!("Line {}: expected TRUE or FALSE, found {:?}", index + 1, flag));
+            }
+        }
+
+        let expiry: i64 = expiry.parse()
+            .map_err(|_| format!("Line {}: invalid expiry {:?}", index + 1, expiry))?;
+
+        cookies += 1;
+
+        let is_youtube = ["youtube.com", "google.com"].iter().any(|site| is_domain_of(domain, site));
+        if is_youtube && LOGIN_COOKIES.contains(&name) {
+            if !login_cookies.iter().any(|n| n == name) {
+                login_cookies.push(name.to_string());
+            }
+            if expiry > 0 {
+                expires_at = Some(expires_at.map_or(expiry, |e| e.min(expiry)));
+            }
+        }
+    }
+
+    if cookies == 0 {
+        return Err("No cookies found in Netscape cookie file".to_string());
+    }
+
+    Ok(CookieJarSummary {
+        cookies,
+        login_cookies,
+        expires_at,
+    })
+}
+
+pub struct CachedCookieJar {
+    modified: SystemTime,
+    len: u64,
+    summary: Option<CookieJarSummary>,
+}
+
+/// Parses a cookie jar once per modification; status pages only stat the file.
+pub fn summarize_cookie_file(state: &AppState, path: &str) -> Option<CookieJarSummary> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?;
+    if let Some(cached) = state.cookie_summaries.get(path) {
+        if cached.modified == modified && cached.len == metadata.len() {
+            return cached.summary.clone();
+        }
+    }
+
+    let summary = std::fs::read_to_string(path).ok()
+        .and_then(|content| parse_netscape_cookies(&content).ok());
+    state.cookie_summaries.insert(path.to_string(), CachedCookieJar {
+        modified,
+        len: metadata.len(),
+        summary: summary.clone(),
+    });
+    summary
+}
+
+pub fn select_cookies(state: &AppState) -> Option<String> {
+    let available = get_available_cookies();
+    let now = Utc::now().timestamp_millis();
+
+    let mut healthy: Vec<(String, u32)> = Vec::new();
+    let mut soonest_released: Option<(String, i64)> = None;
+    for path in available {
+        match state.cookie_health.get(&path).map(|health| (health.quarantined_until, health.consecutive_failures)) {
+            Some((until, _)) if until > now => {
+                if soonest_released.as_ref().is_none_or(|(_, soonest)| until < *soonest) {
+                    soonest_released = Some((path, until));
+                }
+            }
+            Some((_, failures)) => healthy.push((path, failures)),
+            None => healthy.push((path, 0)),
+        }
+    }
+
+    let Some(best) = healthy.iter().map(|(_, failures)| *failures).min() else {
+        if let Some((path, until)) = &soonest_released {
+            tracing::warn!(cookie_file = %path, quarantined_for_ms = until - now, "all cookie jars are quarantined; using the one released soonest");
+        }
+        return soonest_released.map(|(path, _)| path);
+    };
+    let candidates: Vec<&String> = healthy.iter()
+        .filter(|(_, failures)| *failures == best)
+        .map(|(path, _)| path)
+        .collect();
+
+    candidates.choose(&mut rand::thread_rng()).map(|path| (*path).clone())
+}
+
+pub fn record_cookie_success(state: &AppState, path: &str) {
+    let mut health = state.cookie_health.entry(path.to_string()).or_default();
+    health.successes += 1;
+    health.consecutive_failures = 0;
+    health.quarantined_until = 0;
+    health.last_success = Utc::now().timestamp_millis();
+}
+
+pub fn record_cookie_failure(state: &AppState, path: &str, error: YtdlpError) {
+    let now = Utc::now().timestamp_millis();
+    let mut health = state.cookie_health.entry(path.to_string()).or_default();
+    health.failures += 1;
+    health.consecutive_failures += 1;
+    health.last_failure = now;
+    health.last_error = error.code().to_string();
+
+    let exponent = health.consecutive_failures.saturating_sub(1).min(16);
+    let backoff = get_cookie_quarantine_base()
+        .saturating_mul(1 << exponent)
+        .min(get_cookie_quarantine_max());
+    health.quarantined_until = now + backoff * 1000;
+}
+
+pub fn cookie_report(state: &AppState) -> Vec<CookieFileInfo> {
+    let now = Utc::now().timestamp_millis();
+    let warn_before = get_cookie_expiry_warning();
+
+    let available = get_available_cookies();
+    state.cookie_summaries.retain(|path, _| available.contains(path));
+
+    available
+        .into_iter()
+        .map(|path| {
+            let health = state.cookie_health.get(&path).map(|h| h.clone()).unwrap_or_default();
+            let file = Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let summary = summarize_cookie_file(state, &path);
+            let valid = summary.is_some();
+            let (login_cookies, expires_at) = summary
+                .map(|s| (s.login_cookies, s.expires_at))
+                .unwrap_or_default();
+
+            CookieFileInfo {
+                file,
+                valid,
+                login_cookies,
+                expires_at,
+                expiring_soon: expires_at.is_some_and(|e| e - now / 1000 < warn_before),
+                healthy: health.quarantined_until <= now,
+                successes: health.successes,
+                failures: health.failures,
+                consecutive_failures: health.consecutive_failures,
+                last_error: health.last_error,
+                quarantined_for: ((health.quarantined_until - now) / 1000).max(0),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(domain: &str, name: &str, expiry: i64) -> String {
+        format!("{}\tTRUE\t/\tTRUE\t{}\t{}\tvalue\n", domain, expiry, name)
+    }
+
+    #[test]
+    fn summarizes_login_cookies_and_earliest_expiry() {
+        let content = [
+            "# Netscape HTTP Cookie File\n".to_string(),
+            line(".youtube.com", "SID", 1_900_000_000),
+            format!("#HttpOnly_{}", line(".youtube.com", "__Secure-3PSID", 1_800_000_000)),
+            line(".youtube.com", "PREF", 1_700_000_000),
+            line(".example.com", "SID", 1_600_000_000),
+            line(".google.com", "SID", 1_950_000_000),
+        ].concat();
+
+        let summary = parse_netscape_cookies(&content).unwrap();
+        assert_eq!(summary.cookies, 5);
+        assert_eq!(summary.login_cookies, vec!["SID", "__Secure-3PSID"]);
+        assert_eq!(summary.expires_at, Some(1_800_000_000));
+    }
+
+    #[test]
+    fn lookalike_domains_are_not_youtube() {
+        let content = [
+            line("notyoutube.com", "SID", 1_900_000_000),
+            line(".evilgoogle.com", "HSID", 1_900_000_000),
+            line(".youtube.com.example.net", "SSID", 1_900_000_000),
+            line("youtube.com", "APISID", 1_950_000_000),
+            line("accounts.google.com", "SAPISID", 1_960_000_000),
+        ].concat();
+
+        let summary = parse_netscape_cookies(&content).unwrap();
+        assert_eq!(summary.cookies, 5);
+        assert_eq!(summary.login_cookies, vec!["APISID", "SAPISID"]);
+        assert_eq!(summary.expires_at, Some(1_950_000_000));
+    }
+
+    #[test]
+    fn session_cookies_do_not_set_an_expiry() {
+        let content = format!("{}\r\n", line(".youtube.com", "LOGIN_INFO", 0).trim_end());
+        let summary = parse_netscape_cookies(&content).unwrap();
+        assert_eq!(summary.login_cookies, vec!["LOGIN_INFO"]);
+        assert_eq!(summary.expires_at, None);
+    }
+
+    #[test]
+    fn rejects_malformed_jars() {
+        assert_eq!(parse_netscape_cookies("# only comments\n\n").err().unwrap(), "No cookies found in Netscape cookie file");
+        assert!(parse_netscape_cookies(".youtube.com TRUE / TRUE 0 SID value").err().unwrap().starts_with("Line 1: expected 7"));
+        assert!(parse_netscape_cookies(".youtube.com\tyes\t/\tTRUE\t0\tSID\tvalue").err().unwrap().contains("TRUE or FALSE"));
+        let bad_expiry = format!("# header\n{}", ".youtube.com\tTRUE\t/\tTRUE\tsoon\tSID\tvalue");
+        assert!(parse_netscape_cookies(&bad_expiry).err().unwrap().starts_with("Line 2: invalid expiry"));
+    }
+}
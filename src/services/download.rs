@@ -1,23 +1,22 @@
 use actix_web::web;
-use tokio::fs;
-use tokio::time::{sleep, Duration};
-use uuid::Uuid;
 use chrono::Utc;
-use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::Instrument;
 use dashmap::DashMap;
-use crate::models::{Task, CacheEntry, AppState};
-use crate::config::{get_audio_dir, get_video_dir, get_merge_dir, get_max_video_duration, get_max_audio_duration, get_max_file_size};
-use crate::services::youtube::{get_video_info, get_format_hierarchy, execute_ytdlp};
-use crate::utils::helpers::get_random_cookies;
-
-pub async fn find_file(dir: &str, uuid: &str, ext: &str) -> Option<String> {
-    let expected_path = format!("{}/{}{}", dir, uuid, ext);
-    
-    match fs::metadata(&expected_path).await {
-        Ok(metadata) if metadata.is_file() => Some(expected_path),
-        _ => None,
-    }
-}
+use dashmap::mapref::entry::Entry;
+use std::path::Path;
+use ytdl_core::process::CommandLimits;
+use ytdl_core::{AttemptOutcome, DownloadHooks, DownloadRequest, DownloadResult, Downloader, DownloaderOptions, DownloadError, MediaType, Progress, YtdlpError};
+use crate::models::{Task, CacheEntry, CacheSidecar, AppState};
+use crate::config::{get_audio_dir, get_video_dir, get_merge_dir, get_max_video_duration, get_max_audio_duration, get_max_file_size, get_disk_space_margin, get_process_timeout, get_process_stall_timeout, get_process_cpu_limit, get_process_memory_limit, get_process_open_files_limit, derive_audio, derive_video, validate_outputs, get_duration_tolerance};
+use crate::services::cookies::{select_cookies, record_cookie_success, record_cookie_failure};
+use crate::services::cache::{evict_for_space, write_sidecar};
+use crate::services::webhook::notify_task_webhooks;
+use crate::services::logs::record_attempt_output;
+use crate::services::scheduler::{acquire_slot, Requester};
+use crate::services::proxy::{select_proxy, record_proxy_success, record_proxy_failure};
 
 pub fn update_task_status<F>(tasks: &DashMap<String, Task>, task_id: &str, updater: F)
 where
@@ -28,115 +27,300 @@ where
     }
 }
 
-pub async fn process_download(state: web::Data<AppState>, video_id: String, url: String, media_type: String) {
-    let task_id = format!("{}_{}", video_id, media_type);
-    
-    let dir = match media_type.as_str() {
-        "audio" => get_audio_dir(),
-        "video" => get_video_dir(),
-        "merge" => get_merge_dir(),
-        _ => {
-            update_task_status(&state.tasks, &task_id, |task| {
-                task.status = "failed".to_string();
-                task.error = "Invalid media type processing".to_string();
-            });
-            let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, media_type)));
-            state.cache.remove(&cache_key);
-            sleep(Duration::from_secs(3)).await;
-            state.tasks.remove(&task_id);
-            return;
+/// Counts a job as active from the moment it is spawned until it finishes,
+/// so shutdown never observes a spawned job that has not started yet.
+pub struct JobGuard(web::Data<AppState>);
+
+impl JobGuard {
+    fn new(state: web::Data<AppState>) -> Self {
+        state.active_jobs.fetch_add(1, Ordering::SeqCst);
+        Self(state)
+    }
+}
+
+impl Drop for JobGuard {
+    fn drop(&mut self) {
+        self.0.active_jobs.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn content_key(video_id: &str, media_type: &str) -> String {
+    format!("{}_{}", video_id, media_type)
+}
+
+fn update_job_tasks<F>(state: &AppState, key: &str, updater: F)
+where
+    F: Fn(&mut Task),
+{
+    let task_ids = state.jobs.get(key).map(|ids| ids.clone()).unwrap_or_default();
+    for task_id in task_ids {
+        update_task_status(&state.tasks, &task_id, &updater);
+    }
+}
+
+fn finish_job<F>(state: &web::Data<AppState>, key: &str, updater: F)
+where
+    F: Fn(&mut Task),
+{
+    let task_ids = state.jobs.remove(key).map(|(_, ids)| ids).unwrap_or_default();
+    for task_id in &task_ids {
+        update_task_status(&state.tasks, task_id, &updater);
+        notify_task_webhooks(state, task_id);
+    }
+}
+
+pub fn join_or_spawn_job(state: &web::Data<AppState>, task_id: &str, video_id: &str, url: &str, media_type: &str, requester: Requester, request_id: &str) {
+    let key = content_key(video_id, media_type);
+    let sibling = match state.jobs.entry(key.clone()) {
+        Entry::Occupied(mut entry) => {
+            let sibling = entry.get().first().cloned();
+            entry.get_mut().push(task_id.to_string());
+            sibling
+        }
+        Entry::Vacant(entry) => {
+            entry.insert(vec![task_id.to_string()]);
+            None
         }
     };
 
-    let cookies = get_random_cookies();
-    let cookies_ref = cookies.as_deref();
+    tracing::info!(task_id, job = %key, request_id, client = %requester.client, priority = requester.priority, joined = sibling.is_some(), "task created");
 
-    if let Some((duration, filesize)) = get_video_info(&url, cookies_ref).await {
-        let max_duration = if media_type == "audio" { get_max_audio_duration() } else { get_max_video_duration() };
-        
-        if duration > max_duration as f64 {
-            update_task_status(&state.tasks, &task_id, |task| {
-                task.status = "failed".to_string();
-                task.error = "Duration exceeds maximum".to_string();
+    match sibling.and_then(|id| state.tasks.get(&id).map(|task| (task.status.clone(), task.progress.clone()))) {
+        Some((status, progress)) => {
+            update_task_status(&state.tasks, task_id, |task| {
+                task.status = status;
+                task.progress = progress;
             });
-            let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, media_type)));
-            state.cache.remove(&cache_key);
-            sleep(Duration::from_secs(3)).await;
-            state.tasks.remove(&task_id);
-            return;
+            state.scheduler.raise_priority(&key, requester.priority);
         }
-        
-        let max_file_size = get_max_file_size();
-        if filesize > 0 && filesize > max_file_size {
-            update_task_status(&state.tasks, &task_id, |task| {
-                task.status = "failed".to_string();
-                task.error = "File size exceeds maximum".to_string();
-            });
-            let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, media_type)));
-            state.cache.remove(&cache_key);
-            sleep(Duration::from_secs(3)).await;
-            state.tasks.remove(&task_id);
-            return;
+        None => spawn_download(state.clone(), key, video_id.to_string(), url.to_string(), media_type.to_string(), requester, request_id),
+    }
+}
+
+fn spawn_download(state: web::Data<AppState>, key: String, video_id: String, url: String, media_type: String, requester: Requester, request_id: &str) {
+    let span = tracing::info_span!("job", job = %key, request_id = %request_id);
+    tracing::info!(parent: &span, url = %url, media_type = %media_type, "download job started");
+
+    let job = JobGuard::new(state.clone());
+    tokio::spawn(async move {
+        process_download(state, video_id, url, media_type, requester, job).await;
+    }.instrument(span));
+}
+
+fn fail_job(state: &web::Data<AppState>, key: &str, error_code: &str, error: &str) {
+    let finished_at = Utc::now().timestamp_millis();
+    state.cache.remove(&format!("{:x}", md5::compute(key)));
+    finish_job(state, key, |task| {
+        task.status = "failed".to_string();
+        task.error = error.to_string();
+        task.error_code = error_code.to_string();
+        task.finished_at = finished_at;
+    });
+}
+
+fn interrupt_job(state: &AppState, key: &str) {
+    tracing::warn!("download interrupted by shutdown; tasks will be resumed on next start");
+    update_job_tasks(state, key, |task| {
+        task.status = "interrupted".to_string();
+        task.progress = "0%".to_string();
+    });
+}
+
+fn complete_job(state: &web::Data<AppState>, key: &str, entry: &CacheEntry) {
+    let file_url = format!("/files/{}", Path::new(&entry.file_path).file_name().unwrap_or_default().to_string_lossy());
+    let finished_at = Utc::now().timestamp_millis();
+    finish_job(state, key, |task| {
+        task.status = "completed".to_string();
+        task.progress = "100%".to_string();
+        task.file_path = entry.file_path.clone();
+        task.file_url = file_url.clone();
+        task.media_info = entry.media_info.clone();
+        task.finished_at = finished_at;
+    });
+}
+
+impl DownloadHooks for AppState {
+    fn select_cookies(&self) -> Option<String> {
+        select_cookies(self)
+    }
+
+    fn select_proxy(&self, video_id: &str, cookies: Option<&str>) -> Option<String> {
+        select_proxy(self, video_id, cookies)
+    }
+
+    fn cookies_succeeded(&self, cookies: &str) {
+        record_cookie_success(self, cookies);
+    }
+
+    fn cookies_failed(&self, cookies: &str, error: YtdlpError) {
+        record_cookie_failure(self, cookies, error);
+    }
+
+    fn proxy_succeeded(&self, proxy: &str) {
+        record_proxy_success(self, proxy);
+    }
+
+    fn proxy_failed(&self, proxy: &str, error: YtdlpError) {
+        record_proxy_failure(self, proxy, error);
+    }
+
+    fn ytdlp_failed(&self, error: YtdlpError) {
+        self.metrics.ytdlp_failures.with_label_values(&[error.code()]).inc();
+    }
+
+    fn attempt_finished(&self, media_type: MediaType, attempt: usize, outcome: AttemptOutcome) {
+        self.metrics.format_attempts
+            .with_label_values(&[media_type.as_str(), &attempt.to_string(), outcome.label()])
+            .inc();
+    }
+
+    fn reclaim_space(&self, dir: &Path, bytes: u64, keep: Option<&Path>) -> u64 {
+        evict_for_space(self, dir, bytes, keep)
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+pub fn media_dir(media_type: MediaType) -> String {
+    match media_type {
+        MediaType::Audio => get_audio_dir(),
+        MediaType::Video => get_video_dir(),
+        MediaType::Merge => get_merge_dir(),
+    }
+}
+
+pub fn command_limits() -> CommandLimits {
+    let enabled = |value: u64| Some(value).filter(|value| *value > 0);
+    CommandLimits {
+        timeout: enabled(get_process_timeout()).map(Duration::from_secs),
+        stall_timeout: enabled(get_process_stall_timeout()).map(Duration::from_secs),
+        cpu_seconds: enabled(get_process_cpu_limit()),
+        memory_bytes: enabled(get_process_memory_limit()),
+        open_files: enabled(get_process_open_files_limit()),
+    }
+}
+
+pub fn build_downloader(state: Arc<AppState>) -> Downloader {
+    let options = DownloaderOptions {
+        concurrent_fragments: state.max_concurrent,
+        ffmpeg_path: state.ffmpeg_path.clone(),
+        ffprobe_path: Some(state.ffprobe_path.clone()).filter(|path| validate_outputs() && !path.is_empty()),
+        duration_tolerance: get_duration_tolerance(),
+        max_video_duration: Some(get_max_video_duration()),
+        max_audio_duration: Some(get_max_audio_duration()),
+        max_file_size: Some(get_max_file_size()),
+        disk_space_margin: get_disk_space_margin(),
+        limits: command_limits(),
+    };
+    Downloader::new(options).with_hooks(state)
+}
+
+async fn derive_from_cache<F>(state: &AppState, downloader: &Downloader, video_id: &str, request: &DownloadRequest, on_progress: F) -> Option<Result<DownloadResult, DownloadError>>
+where
+    F: Fn(Progress),
+{
+    let enabled = match request.media_type {
+        MediaType::Audio => derive_audio(),
+        MediaType::Video => derive_video(),
+        MediaType::Merge => false,
+    };
+    if !enabled {
+        return None;
+    }
+
+    for source_type in request.media_type.derivable_from() {
+        let cache_key = format!("{:x}", md5::compute(content_key(video_id, source_type.as_str())));
+        let source = match state.cache.get(&cache_key) {
+            Some(entry) if Path::new(&entry.file_path).exists() => entry.file_path.clone(),
+            _ => continue,
+        };
+
+        match downloader.derive(Path::new(&source), *source_type, request, &on_progress).await {
+            Err(DownloadError::Transcode) => continue,
+            result => return Some(result),
         }
     }
 
-    let filename = Uuid::new_v4().to_string();
-    let format_hierarchy = get_format_hierarchy(&media_type);
+    None
+}
+
+pub async fn process_download(state: web::Data<AppState>, video_id: String, url: String, media_type: String, requester: Requester, _job: JobGuard) {
+    let key = content_key(&video_id, &media_type);
+
+    let cache_key = format!("{:x}", md5::compute(&key));
+    if let Some(entry) = state.cache.get(&cache_key).map(|entry| entry.clone()).filter(|entry| Path::new(&entry.file_path).exists()) {
+        complete_job(&state, &key, &entry);
+        return;
+    }
     
-    let mut success = false;
-    let mut final_file_path = String::new();
+    let kind = match media_type.parse::<MediaType>() {
+        Ok(kind) => kind,
+        Err(error) => {
+            fail_job(&state, &key, error.code(), "Invalid media type processing");
+            return;
+        }
+    };
 
-    update_task_status(&state.tasks, &task_id, |task| {
+    let Some(_slot) = acquire_slot(&state, &key, &requester).await else {
+        interrupt_job(&state, &key);
+        return;
+    };
+    update_job_tasks(&state, &key, |task| {
         task.status = "downloading".to_string();
         task.progress = "50%".to_string();
     });
 
-    for (format, ext, post_proc) in format_hierarchy {
-        let output_template = format!("{}/{}.%(ext)s", dir, filename);
-
-        match execute_ytdlp(&url, format, &output_template, post_proc, state.max_concurrent, &state.ffmpeg_path, cookies_ref).await {
-            Ok(_) => {
-                update_task_status(&state.tasks, &task_id, |task| {
-                    task.status = "processing".to_string();
-                    task.progress = "100%".to_string();
-                });
-
-                if let Some(file_path) = find_file(&dir, &filename, ext).await {
-                    final_file_path = file_path;
-                    success = true;
-                    break;
-                } else {
-                    continue;
-                }
+    let request = DownloadRequest::new(url, kind, media_dir(kind));
+    let downloader = build_downloader(state.clone().into_inner());
+    let on_progress = |progress| {
+        let (status, percent) = match progress {
+            Progress::Probing | Progress::Percent { .. } => return,
+            Progress::Output { attempt, format, error, stdout, stderr } => {
+                let task_ids = state.jobs.get(&key).map(|ids| ids.clone()).unwrap_or_default();
+                record_attempt_output(&state, &task_ids, attempt, &format, error, &stdout, &stderr);
+                return;
             }
-            Err(_) => continue,
-        }
-    }
-
-    if success {
-        let file_url = format!("/files/{}", PathBuf::from(&final_file_path).file_name().unwrap().to_string_lossy());
-        
-        let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, media_type)));
-        state.cache.insert(cache_key, CacheEntry {
-            file_path: final_file_path.clone(),
-            timestamp: Utc::now().timestamp_millis(),
-            media_type: media_type.clone(),
+            Progress::Downloading { .. } => ("downloading", "50%"),
+            Progress::Processing => ("processing", "100%"),
+        };
+        update_job_tasks(&state, &key, |task| {
+            task.status = status.to_string();
+            task.progress = percent.to_string();
         });
+    };
 
-        update_task_status(&state.tasks, &task_id, |task| {
-            task.status = "completed".to_string();
-            task.progress = "100%".to_string();
-            task.file_path = final_file_path.clone();
-            task.file_url = file_url;
-        });
-    } else {
-        update_task_status(&state.tasks, &task_id, |task| {
-            task.status = "failed".to_string();
-            task.error = "All format attempts failed".to_string();
-        });
-        let cache_key = format!("{:x}", md5::compute(format!("{}_{}", video_id, media_type)));
-        state.cache.remove(&cache_key);
-        sleep(Duration::from_secs(3)).await;
-        state.tasks.remove(&task_id);
+    let result = match derive_from_cache(&state, &downloader, &video_id, &request, on_progress).await {
+        Some(derived) => derived,
+        None => downloader.download(&request, on_progress).await,
+    };
+
+    match result {
+        Ok(download) => {
+            state.metrics.download_duration.with_label_values(&[&media_type]).observe(download.elapsed.as_secs_f64());
+            state.metrics.download_bytes.with_label_values(&[&media_type]).inc_by(download.bytes);
+
+            let entry = CacheEntry {
+                file_path: download.file_path.to_string_lossy().to_string(),
+                timestamp: Utc::now().timestamp_millis(),
+                media_type: media_type.clone(),
+                media_info: download.media_info,
+            };
+            write_sidecar(&entry.file_path, &CacheSidecar {
+                video_id: video_id.clone(),
+                media_type: media_type.clone(),
+                timestamp: entry.timestamp,
+                media_info: entry.media_info.clone(),
+            }).await;
+
+            state.cache.insert(cache_key, entry.clone());
+            complete_job(&state, &key, &entry);
+        }
+        Err(DownloadError::Cancelled) => interrupt_job(&state, &key),
+        Err(error) => {
+            tracing::error!(error_code = error.code(), "download failed");
+            fail_job(&state, &key, error.code(), error.message());
+        }
     }
 }
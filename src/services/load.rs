@@ -0,0 +1,66 @@
+use std::fs;
+use crate::config::{get_shed_cpu_load, get_shed_queue_length, get_shed_active_processes, get_shed_retry_after};
+use crate::models::{AppState, ApiError};
+use ytdl_core::process::running_children;
+
+pub struct LoadSnapshot {
+    pub queued: usize,
+    pub cpu_load: Option<f64>,
+    pub active_processes: usize,
+    pub reasons: Vec<&'static str>,
+}
+
+impl LoadSnapshot {
+    pub fn shedding(&self) -> bool {
+        !self.reasons.is_empty()
+    }
+}
+
+fn cpu_load() -> Option<f64> {
+    let loadavg = fs::read_to_string("/proc/loadavg").ok()?;
+    let one_minute: f64 = loadavg.split_whitespace().next()?.parse().ok()?;
+    Some(one_minute / num_cpus::get().max(1) as f64)
+}
+
+pub fn load_snapshot(state: &AppState) -> LoadSnapshot {
+    let queued = state.scheduler.snapshot().queued;
+    let cpu_load = cpu_load();
+    let active_processes = running_children();
+
+    let mut reasons = Vec::new();
+    let queue_limit = get_shed_queue_length();
+    if queue_limit > 0 && queued >= queue_limit {
+        reasons.push("queue_length");
+    }
+    let cpu_limit = get_shed_cpu_load();
+    if cpu_limit > 0.0 && cpu_load.is_some_and(|load| load >= cpu_limit) {
+        reasons.push("cpu_load");
+    }
+    let process_limit = get_shed_active_processes();
+    if process_limit > 0 && active_processes >= process_limit {
+        reasons.push("active_processes");
+    }
+
+    LoadSnapshot { queued, cpu_load, active_processes, reasons }
+}
+
+pub fn shed_load(state: &AppState) -> Result<(), ApiError> {
+    let snapshot = load_snapshot(state);
+    if !snapshot.shedding() {
+        return Ok(());
+    }
+
+    for reason in &snapshot.reasons {
+        state.metrics.load_shed.with_label_values(&[reason]).inc();
+    }
+    tracing::warn!(
+        reasons = ?snapshot.reasons,
+        queued = snapshot.queued,
+        cpu_load = ?snapshot.cpu_load,
+        active_processes = snapshot.active_processes,
+        "shedding load for uncached request"
+    );
+
+    Err(ApiError::unavailable("overloaded", "Server is overloaded, please retry later", get_shed_retry_after())
+        .with_details(serde_json::json!({"reasons": snapshot.reasons})))
+}
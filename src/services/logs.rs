@@ -0,0 +1,25 @@
+use chrono::Utc;
+use ytdl_core::YtdlpError;
+use ytdl_core::youtube::stderr_excerpt;
+use crate::models::{AppState, TaskLogEntry};
+use crate::config::{get_cookies_dir, get_task_log_max_bytes};
+
+pub fn redact_output(output: &str) -> String {
+    ytdl_core::redact_output(output, &get_cookies_dir())
+}
+
+pub fn record_attempt_output(state: &AppState, task_ids: &[String], attempt: usize, format: &str, error: Option<YtdlpError>, stdout: &str, stderr: &str) {
+    let max_bytes = get_task_log_max_bytes();
+    let entry = TaskLogEntry {
+        timestamp: Utc::now().timestamp_millis(),
+        attempt,
+        format: format.to_string(),
+        error_code: error.map(|e| e.code().to_string()).unwrap_or_default(),
+        stdout: stderr_excerpt(&redact_output(stdout), max_bytes),
+        stderr: stderr_excerpt(&redact_output(stderr), max_bytes),
+    };
+
+    for task_id in task_ids {
+        state.task_logs.entry(task_id.clone()).or_default().push(entry.clone());
+    }
+}
@@ -0,0 +1,94 @@
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec,
+    pub http_request_duration: HistogramVec,
+    pub tasks: IntGaugeVec,
+    pub queue_depth: IntGauge,
+    pub download_duration: HistogramVec,
+    pub download_bytes: IntCounterVec,
+    pub format_attempts: IntCounterVec,
+    pub cache_hits: IntCounter,
+    pub cache_misses: IntCounter,
+    pub cache_evictions: IntCounter,
+    pub disk_usage: IntGaugeVec,
+    pub pow_issued: IntCounter,
+    pub pow_verified: IntCounter,
+    pub ytdlp_failures: IntCounterVec,
+    pub load_shed: IntCounterVec,
+}
+
+fn register<T: prometheus::core::Collector + Clone + 'static>(registry: &Registry, metric: T) -> T {
+    registry.register(Box::new(metric.clone())).expect("metric registered twice");
+    metric
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new_custom(Some("ytdl".to_string()), None).expect("valid metrics prefix");
+
+        Self {
+            http_requests: register(&registry, IntCounterVec::new(
+                Opts::new("http_requests_total", "HTTP requests by route, method and status"),
+                &["route", "method", "status"],
+            ).unwrap()),
+            http_request_duration: register(&registry, HistogramVec::new(
+                HistogramOpts::new("http_request_duration_seconds", "HTTP request latency by route, method and status"),
+                &["route", "method", "status"],
+            ).unwrap()),
+            tasks: register(&registry, IntGaugeVec::new(
+                Opts::new("tasks", "Tasks by state"),
+                &["state"],
+            ).unwrap()),
+            queue_depth: register(&registry, IntGauge::new(
+                "queue_depth", "Download jobs waiting for a worker slot",
+            ).unwrap()),
+            download_duration: register(&registry, HistogramVec::new(
+                HistogramOpts::new("download_duration_seconds", "Wall-clock duration of completed downloads")
+                    .buckets(vec![1.0, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1800.0, 3600.0]),
+                &["media_type"],
+            ).unwrap()),
+            download_bytes: register(&registry, IntCounterVec::new(
+                Opts::new("download_bytes_total", "Bytes written by completed downloads"),
+                &["media_type"],
+            ).unwrap()),
+            format_attempts: register(&registry, IntCounterVec::new(
+                Opts::new("format_attempts_total", "yt-dlp format attempts by position in the fallback hierarchy"),
+                &["media_type", "attempt", "result"],
+            ).unwrap()),
+            cache_hits: register(&registry, IntCounter::new("cache_hits_total", "Download requests served from cache").unwrap()),
+            cache_misses: register(&registry, IntCounter::new("cache_misses_total", "Download requests not found in cache").unwrap()),
+            cache_evictions: register(&registry, IntCounter::new("cache_evictions_total", "Cache entries evicted").unwrap()),
+            disk_usage: register(&registry, IntGaugeVec::new(
+                Opts::new("disk_usage_bytes", "Bytes used by each media directory"),
+                &["media_type"],
+            ).unwrap()),
+            pow_issued: register(&registry, IntCounter::new("pow_challenges_issued_total", "PoW challenges issued").unwrap()),
+            pow_verified: register(&registry, IntCounter::new("pow_challenges_verified_total", "PoW challenges verified").unwrap()),
+            ytdlp_failures: register(&registry, IntCounterVec::new(
+                Opts::new("ytdlp_failures_total", "yt-dlp failures by error class"),
+                &["error_class"],
+            ).unwrap()),
+            load_shed: register(&registry, IntCounterVec::new(
+                Opts::new("load_shed_total", "Uncached requests rejected by load shedding, by threshold"),
+                &["reason"],
+            ).unwrap()),
+            registry,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        let _ = TextEncoder::new().encode(&self.registry.gather(), &mut buffer);
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,7 +1,17 @@
-pub mod youtube;
 pub mod download;
 pub mod cache;
+pub mod cookies;
+pub mod proxy;
+pub mod metrics;
+pub mod webhook;
+pub mod shutdown;
+pub mod logs;
+pub mod scheduler;
+pub mod load;
 
-pub use youtube::*;
 pub use download::*;
 pub use cache::*;
+pub use cookies::*;
+pub use proxy::*;
+pub use webhook::*;
+pub use shutdown::*;
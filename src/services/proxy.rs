@@ -0,0 +1,162 @@
+use actix_web::web;
+use ytdl_core::process::{run_command, CommandLimits};
+use tokio::time::{sleep, Duration};
+use chrono::Utc;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use crate::models::{AppState, ProxyStatus};
+use crate::config::{get_proxies, get_proxy_strategy, get_proxy_cookie_pairs, get_proxy_failure_threshold, get_proxy_probe_interval, get_proxy_probe_url};
+use ytdl_core::YtdlpError;
+use crate::utils::redact_proxy;
+
+fn is_proxy_up(state: &AppState, proxy: &str) -> bool {
+    state.proxy_health.get(proxy).is_none_or(|h| !h.down)
+}
+
+pub fn select_proxy(state: &AppState, video_id: &str, cookies: Option<&str>) -> Option<String> {
+    let proxies = get_proxies();
+    if proxies.is_empty() {
+        return None;
+    }
+
+    if let Some(cookie_name) = cookies.and_then(|c| Path::new(c).file_name()).map(|n| n.to_string_lossy().to_string()) {
+        if let Some(proxy) = get_proxy_cookie_pairs().get(&cookie_name) {
+            if is_proxy_up(state, proxy) {
+                return Some(proxy.clone());
+            }
+        }
+    }
+
+    let up: Vec<&String> = proxies.iter().filter(|p| is_proxy_up(state, p)).collect();
+    let pool: Vec<&String> = if up.is_empty() { proxies.iter().collect() } else { up };
+
+    let selected = match get_proxy_strategy().as_str() {
+        "least_failures" => pool.iter()
+            .min_by_key(|p| state.proxy_health.get(p.as_str()).map(|h| h.failures).unwrap_or(0))
+            .copied(),
+        "sticky" => {
+            // Hash over the configured list so a proxy going down only moves
+            // the videos pinned to it; skip forward to the next proxy that is up.
+            let digest = md5::compute(video_id.as_bytes());
+            let hash = u64::from_le_bytes(digest.0[..8].try_into().unwrap_or_default());
+            let start = (hash % proxies.len() as u64) as usize;
+            (0..proxies.len())
+                .map(|offset| &proxies[(start + offset) % proxies.len()])
+                .find(|p| is_proxy_up(state, p))
+                .or(proxies.get(start))
+        }
+        _ => {
+            let index = state.proxy_cursor.fetch_add(1, Ordering::Relaxed);
+            pool.get(index % pool.len()).copied()
+        }
+    };
+
+    selected.cloned()
+}
+
+pub fn record_proxy_success(state: &AppState, proxy: &str) {
+    let mut health = state.proxy_health.entry(proxy.to_string()).or_default();
+    health.successes += 1;
+    health.consecutive_failures = 0;
+    health.down = false;
+}
+
+pub fn record_proxy_failure(state: &AppState, proxy: &str, error: YtdlpError) {
+    let mut health = state.proxy_health.entry(proxy.to_string()).or_default();
+    health.failures += 1;
+    health.consecutive_failures += 1;
+    health.last_error = error.code().to_string();
+    if !health.down && health.consecutive_failures >= get_proxy_failure_threshold() {
+        health.down = true;
+        health.down_since = Utc::now().timestamp_millis();
+        tracing::warn!(proxy = %redact_proxy(proxy), consecutive_failures = health.consecutive_failures, error = error.code(), "proxy marked down");
+    }
+}
+
+async fn probe_proxy(proxy: &str) -> bool {
+    let args: Vec<String> = [
+        "--proxy", proxy,
+        "--simulate",
+        "--no-playlist",
+        "--no-warnings",
+        "--quiet",
+        "--socket-timeout", "15",
+        &get_proxy_probe_url(),
+    ].iter().map(|s| s.to_string()).collect();
+
+    let limits = CommandLimits {
+        timeout: Some(Duration::from_secs(60)),
+        ..Default::default()
+    };
+    let result = run_command("yt-dlp", &args, &limits).await;
+
+    matches!(result, Ok(output) if output.status.success())
+}
+
+/// Drops health entries for proxies that a config reload removed, so they
+/// are neither probed nor kept around forever.
+fn forget_unconfigured_proxies(state: &AppState, configured: &[String]) {
+    state.proxy_health.retain(|proxy, _| configured.contains(proxy));
+}
+
+pub async fn probe_proxies(state: web::Data<AppState>) {
+    loop {
+        sleep(Duration::from_secs(get_proxy_probe_interval())).await;
+
+        forget_unconfigured_proxies(&state, &get_proxies());
+
+        let down: Vec<String> = state.proxy_health.iter()
+            .filter(|entry| entry.down)
+            .map(|entry| entry.key().clone())
+            .collect();
+
+        for proxy in down {
+            if probe_proxy(&proxy).await {
+                if let Some(mut health) = state.proxy_health.get_mut(&proxy) {
+                    health.down = false;
+                    health.consecutive_failures = 0;
+                }
+            }
+        }
+    }
+}
+
+pub fn proxy_report(state: &AppState) -> Vec<ProxyStatus> {
+    let now = Utc::now().timestamp_millis();
+
+    get_proxies()
+        .into_iter()
+        .map(|proxy| {
+            let health = state.proxy_health.get(&proxy).map(|h| h.clone()).unwrap_or_default();
+
+            ProxyStatus {
+                proxy: redact_proxy(&proxy),
+                up: !health.down,
+                successes: health.successes,
+                failures: health.failures,
+                consecutive_failures: health.consecutive_failures,
+                last_error: health.last_error,
+                down_for: if health.down { (now - health.down_since) / 1000 } else { 0 },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_proxies_are_forgotten() {
+        let state = AppState::new(1, String::new(), String::new());
+        let kept = "http://kept:8080".to_string();
+        let removed = "http://removed:8080".to_string();
+        record_proxy_failure(&state, &kept, YtdlpError::Network);
+        record_proxy_failure(&state, &removed, YtdlpError::Network);
+
+        forget_unconfigured_proxies(&state, std::slice::from_ref(&kept));
+
+        assert!(state.proxy_health.contains_key(&kept));
+        assert!(!state.proxy_health.contains_key(&removed));
+    }
+}
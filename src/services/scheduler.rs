@@ -0,0 +1,332 @@
+use actix_web::web;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+use crate::models::AppState;
+use crate::config::{get_priority_aging, get_reserved_premium_slots};
+
+pub const FREE_PRIORITY: i64 = 0;
+
+#[derive(Clone, Debug, Default)]
+pub struct Requester {
+    pub client: String,
+    pub priority: i64,
+}
+
+struct Waiter {
+    key: String,
+    client: String,
+    priority: i64,
+    enqueued_at: Instant,
+    seq: u64,
+    wake: oneshot::Sender<Option<bool>>,
+}
+
+impl Waiter {
+    fn premium(&self) -> bool {
+        self.priority > FREE_PRIORITY
+    }
+
+    fn effective_priority(&self, now: Instant, aging: u64) -> i64 {
+        match aging {
+            0 => self.priority,
+            aging => self.priority + (now.duration_since(self.enqueued_at).as_secs() / aging) as i64,
+        }
+    }
+
+    fn priority_class(&self, classes: &[i64], now: Instant, aging: u64) -> i64 {
+        let aged = self.effective_priority(now, aging);
+        classes.iter().rev().copied().find(|class| *class <= aged).unwrap_or(self.priority)
+    }
+}
+
+#[derive(Default)]
+struct Slots {
+    running: usize,
+    running_free: usize,
+    running_by_client: HashMap<String, usize>,
+    last_served: HashMap<String, u64>,
+    waiters: Vec<Waiter>,
+    seq: u64,
+    turn: u64,
+}
+
+impl Slots {
+    fn client_running(&self, client: &str) -> usize {
+        self.running_by_client.get(client).copied().unwrap_or(0)
+    }
+
+    fn client_last_served(&self, client: &str) -> u64 {
+        self.last_served.get(client).copied().unwrap_or(0)
+    }
+
+    fn next_waiter(&self, now: Instant, aging: u64, free_allowed: bool) -> Option<usize> {
+        let eligible = |waiter: &&Waiter| waiter.premium() || free_allowed;
+        let mut classes: Vec<i64> = self.waiters.iter().filter(eligible).map(|waiter| waiter.priority).collect();
+        classes.sort_unstable();
+        classes.dedup();
+
+        self.waiters.iter()
+            .enumerate()
+            .filter(|(_, waiter)| eligible(waiter))
+            .max_by(|(_, a), (_, b)| {
+                a.priority_class(&classes, now, aging).cmp(&b.priority_class(&classes, now, aging))
+                    .then_with(|| self.client_running(&b.client).cmp(&self.client_running(&a.client)))
+                    .then_with(|| self.client_last_served(&b.client).cmp(&self.client_last_served(&a.client)))
+                    .then_with(|| b.seq.cmp(&a.seq))
+            })
+            .map(|(index, _)| index)
+    }
+
+    fn prune_clients(&mut self) {
+        let Slots { running_by_client, last_served, waiters, .. } = self;
+        running_by_client.retain(|_, running| *running > 0);
+        last_served.retain(|client, _| {
+            running_by_client.contains_key(client) || waiters.iter().any(|waiter| &waiter.client == client)
+        });
+    }
+}
+
+pub struct Scheduler {
+    workers: usize,
+    slots: Mutex<Slots>,
+}
+
+pub struct SchedulerSnapshot {
+    pub workers: usize,
+    pub reserved_premium: usize,
+    pub running: usize,
+    pub queued: usize,
+}
+
+pub struct SlotGuard {
+    state: web::Data<AppState>,
+    client: String,
+    premium: bool,
+}
+
+impl Drop for SlotGuard {
+    fn drop(&mut self) {
+        self.state.scheduler.release(&self.client, self.premium);
+    }
+}
+
+impl Scheduler {
+    pub fn new(workers: usize) -> Self {
+        Self {
+            workers,
+            slots: Mutex::new(Slots::default()),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Slots> {
+        self.slots.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn free_limit(&self) -> usize {
+        self.workers.saturating_sub(get_reserved_premium_slots())
+    }
+
+    fn dispatch(&self, slots: &mut Slots) {
+        let now = Instant::now();
+        let aging = get_priority_aging();
+        let free_limit = self.free_limit();
+
+        while slots.running < self.workers {
+            let free_allowed = slots.running_free < free_limit;
+            let Some(index) = slots.next_waiter(now, aging, free_allowed) else {
+                break;
+            };
+            let waiter = slots.waiters.remove(index);
+            let premium = waiter.premium();
+            if waiter.wake.send(Some(premium)).is_ok() {
+                slots.running += 1;
+                if !premium {
+                    slots.running_free += 1;
+                }
+                slots.turn += 1;
+                let turn = slots.turn;
+                slots.last_served.insert(waiter.client.clone(), turn);
+                *slots.running_by_client.entry(waiter.client).or_default() += 1;
+            }
+        }
+        slots.prune_clients();
+    }
+
+    fn release(&self, client: &str, premium: bool) {
+        let mut slots = self.lock();
+        slots.running = slots.running.saturating_sub(1);
+        if !premium {
+            slots.running_free = slots.running_free.saturating_sub(1);
+        }
+        if let Some(running) = slots.running_by_client.get_mut(client) {
+            *running = running.saturating_sub(1);
+        }
+        self.dispatch(&mut slots);
+    }
+
+    pub fn raise_priority(&self, key: &str, priority: i64) {
+        let mut slots = self.lock();
+        if let Some(waiter) = slots.waiters.iter_mut().find(|waiter| waiter.key == key) {
+            waiter.priority = waiter.priority.max(priority);
+        }
+        self.dispatch(&mut slots);
+    }
+
+    pub fn cancel_all(&self) {
+        let mut slots = self.lock();
+        for waiter in slots.waiters.drain(..) {
+            let _ = waiter.wake.send(None);
+        }
+    }
+
+    pub fn snapshot(&self) -> SchedulerSnapshot {
+        let slots = self.lock();
+        SchedulerSnapshot {
+            workers: self.workers,
+            reserved_premium: get_reserved_premium_slots().min(self.workers),
+            running: slots.running,
+            queued: slots.waiters.len(),
+        }
+    }
+}
+
+pub async fn acquire_slot(state: &web::Data<AppState>, key: &str, requester: &Requester) -> Option<SlotGuard> {
+    let receiver = {
+        let mut slots = state.scheduler.lock();
+        // Checked under the lock: once cancel_all has drained the queue no
+        // one would wake a new waiter, and shutdown would wait it out.
+        if state.shutting_down.load(Ordering::SeqCst) {
+            return None;
+        }
+        let (wake, receiver) = oneshot::channel();
+        slots.seq += 1;
+        let seq = slots.seq;
+        slots.waiters.push(Waiter {
+            key: key.to_string(),
+            client: requester.client.clone(),
+            priority: requester.priority,
+            enqueued_at: Instant::now(),
+            seq,
+            wake,
+        });
+        state.scheduler.dispatch(&mut slots);
+        receiver
+    };
+
+    match receiver.await {
+        Ok(Some(premium)) => Some(SlotGuard {
+            state: state.clone(),
+            client: requester.client.clone(),
+            premium,
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::time::Duration;
+
+    fn push(slots: &mut Slots, key: &str, client: &str, priority: i64, waited_secs: u64, now: Instant) {
+        let (wake, _) = oneshot::channel();
+        slots.seq += 1;
+        let seq = slots.seq;
+        slots.waiters.push(Waiter {
+            key: key.to_string(),
+            client: client.to_string(),
+            priority,
+            enqueued_at: now - Duration::from_secs(waited_secs),
+            seq,
+            wake,
+        });
+    }
+
+    fn next_key(slots: &Slots, now: Instant, aging: u64, free_allowed: bool) -> Option<&str> {
+        slots.next_waiter(now, aging, free_allowed).map(|index| slots.waiters[index].key.as_str())
+    }
+
+    #[test]
+    fn premium_runs_before_free() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        push(&mut slots, "free", "ip_a", FREE_PRIORITY, 30, now);
+        push(&mut slots, "premium", "key_b", 10, 0, now);
+
+        assert_eq!(next_key(&slots, now, 60, true), Some("premium"));
+    }
+
+    #[test]
+    fn round_robin_within_a_class_ignores_aging() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        slots.running_by_client.insert("ip_heavy".to_string(), 1);
+        slots.last_served.insert("ip_heavy".to_string(), 1);
+        push(&mut slots, "heavy-old", "ip_heavy", FREE_PRIORITY, 120, now);
+        push(&mut slots, "idle-new", "ip_idle", FREE_PRIORITY, 0, now);
+
+        assert_eq!(next_key(&slots, now, 60, true), Some("idle-new"));
+    }
+
+    #[test]
+    fn least_recently_served_client_goes_first() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        slots.last_served.insert("key_a".to_string(), 5);
+        slots.last_served.insert("key_b".to_string(), 2);
+        push(&mut slots, "a", "key_a", 10, 10, now);
+        push(&mut slots, "b", "key_b", 10, 0, now);
+
+        assert_eq!(next_key(&slots, now, 0, true), Some("b"));
+    }
+
+    #[test]
+    fn aging_promotes_free_jobs_into_the_premium_class() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        push(&mut slots, "free-starved", "ip_a", FREE_PRIORITY, 11, now);
+        push(&mut slots, "premium", "key_b", 10, 0, now);
+
+        assert_eq!(next_key(&slots, now, 0, true), Some("premium"));
+        assert_eq!(next_key(&slots, now, 1, true), Some("free-starved"));
+    }
+
+    #[test]
+    fn reserved_slots_hold_back_free_jobs() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        push(&mut slots, "free", "ip_a", FREE_PRIORITY, 0, now);
+
+        assert_eq!(next_key(&slots, now, 60, false), None);
+        push(&mut slots, "premium", "key_b", 10, 0, now);
+        assert_eq!(next_key(&slots, now, 60, false), Some("premium"));
+    }
+
+    #[test]
+    fn oldest_waiter_breaks_remaining_ties() {
+        let now = Instant::now();
+        let mut slots = Slots::default();
+        push(&mut slots, "first", "key_a", 10, 0, now);
+        push(&mut slots, "second", "key_a", 10, 0, now);
+
+        assert_eq!(next_key(&slots, now, 60, true), Some("first"));
+    }
+
+    #[tokio::test]
+    async fn jobs_arriving_after_shutdown_are_not_queued() {
+        let state = web::Data::new(AppState::new(1, String::new(), String::new()));
+        state.shutting_down.store(true, Ordering::SeqCst);
+        state.scheduler.cancel_all();
+
+        let requester = Requester { client: "ip_a".to_string(), priority: FREE_PRIORITY };
+        let slot = tokio::time::timeout(Duration::from_secs(1), acquire_slot(&state, "late_merge", &requester)).await;
+
+        assert!(matches!(slot, Ok(None)));
+        assert_eq!(state.scheduler.snapshot().queued, 0);
+        assert_eq!(state.scheduler.snapshot().running, 0);
+    }
+
+}
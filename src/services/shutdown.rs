@@ -0,0 +1,125 @@
+use actix_web::{dev::ServerHandle, web};
+use std::sync::atomic::Ordering;
+use tokio::fs;
+use tokio::time::{sleep, Duration, Instant};
+use crate::models::{AppState, PendingTask};
+use crate::config::{get_pending_tasks_file, get_shutdown_timeout, get_shutdown_kill_grace, get_webhook_timeout};
+use crate::services::download::{content_key, join_or_spawn_job};
+use crate::services::scheduler::Requester;
+use crate::services::webhook::drain_webhooks;
+use ytdl_core::extract_video_id;
+use ytdl_core::process::terminate_children;
+
+async fn wait_for_signal() {
+    #[cfg(unix)]
+    {
+        let mut terminate = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(signal) => signal,
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+async fn wait_for_jobs(state: &AppState, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    while state.active_jobs.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(250)).await;
+    }
+
+    true
+}
+
+async fn persist_pending_tasks(state: &AppState) {
+    let pending: Vec<PendingTask> = state.tasks.iter()
+        .filter(|task| task.status != "completed" && task.status != "failed")
+        .map(|task| PendingTask {
+            task: task.clone(),
+            callbacks: state.task_callbacks.get(task.key()).map(|c| c.clone()).unwrap_or_default(),
+            priority: task.priority,
+            client: task.client.clone(),
+        })
+        .collect();
+
+    if pending.is_empty() {
+        return;
+    }
+
+    let path = get_pending_tasks_file();
+    match serde_json::to_vec(&pending) {
+        Ok(json) => match fs::write(&path, json).await {
+            Ok(()) => tracing::info!(count = pending.len(), path = %path, "persisted unfinished tasks"),
+            Err(e) => tracing::error!(error = %e, path = %path, "failed to persist unfinished tasks"),
+        },
+        Err(e) => tracing::error!(error = %e, "failed to serialize unfinished tasks"),
+    }
+}
+
+pub async fn handle_shutdown(state: web::Data<AppState>, server: ServerHandle) {
+    wait_for_signal().await;
+
+    state.shutting_down.store(true, Ordering::SeqCst);
+    state.scheduler.cancel_all();
+    let timeout = Duration::from_secs(get_shutdown_timeout());
+    tracing::info!(
+        active_jobs = state.active_jobs.load(Ordering::SeqCst),
+        timeout_secs = timeout.as_secs(),
+        "shutdown requested; draining downloads"
+    );
+
+    if !wait_for_jobs(&state, timeout).await {
+        let grace = Duration::from_secs(get_shutdown_kill_grace());
+        terminate_children(grace).await;
+        wait_for_jobs(&state, grace).await;
+    }
+
+    persist_pending_tasks(&state).await;
+    if !drain_webhooks(&state, Duration::from_secs(get_webhook_timeout() + 1)).await {
+        tracing::warn!("webhook deliveries still in flight; they will be retried on next start");
+    }
+    server.stop(true).await;
+}
+
+pub async fn resume_pending_tasks(state: web::Data<AppState>) {
+    let path = get_pending_tasks_file();
+    let pending: Vec<PendingTask> = match fs::read(&path).await {
+        Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+        Err(_) => return,
+    };
+    let _ = fs::remove_file(&path).await;
+
+    for PendingTask { mut task, callbacks, priority, client } in pending {
+        let video_id = match extract_video_id(&task.url) {
+            Ok(id) => id,
+            Err(_) => continue,
+        };
+
+        task.status = "queued".to_string();
+        task.progress = "0%".to_string();
+        task.content_key = content_key(&video_id, &task.media_type);
+        task.priority = priority;
+        task.client = client.clone();
+        state.tasks.insert(task.id.clone(), task.clone());
+        if !callbacks.is_empty() {
+            state.task_callbacks.insert(task.id.clone(), callbacks);
+        }
+
+        join_or_spawn_job(&state, &task.id, &video_id, &task.url, &task.media_type, Requester { client, priority }, "");
+    }
+}
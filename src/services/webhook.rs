@@ -0,0 +1,493 @@
+use actix_web::web;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::Url;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
+use tokio::fs;
+use tokio::net::lookup_host;
+use tokio::time::{sleep, Duration, Instant};
+use uuid::Uuid;
+use crate::models::{AppState, WebhookEvent};
+use crate::config::{
+    get_webhook_secret, get_webhook_max_attempts, get_webhook_timeout, get_webhooks_dir, get_apikey_webhook,
+    get_webhook_allow_private,
+};
+
+pub struct DeliverySettings {
+    pub secret: String,
+    pub max_attempts: u32,
+    pub timeout: Duration,
+    pub dir: String,
+    pub backoff_base: Duration,
+    pub allow_private: bool,
+}
+
+impl DeliverySettings {
+    pub fn current() -> Self {
+        Self {
+            secret: get_webhook_secret(),
+            max_attempts: get_webhook_max_attempts(),
+            timeout: Duration::from_secs(get_webhook_timeout()),
+            dir: get_webhooks_dir(),
+            backoff_base: Duration::from_secs(5),
+            allow_private: get_webhook_allow_private(),
+        }
+    }
+}
+
+pub struct DeliveryGuard(web::Data<AppState>);
+
+impl DeliveryGuard {
+    pub fn new(state: &web::Data<AppState>) -> Self {
+        state.webhook_deliveries.fetch_add(1, Ordering::SeqCst);
+        Self(state.clone())
+    }
+}
+
+impl Drop for DeliveryGuard {
+    fn drop(&mut self) {
+        self.0.webhook_deliveries.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub fn is_forbidden_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || a == 0
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_forbidden_ip(IpAddr::V4(mapped)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || (first & 0xfe00) == 0xfc00
+                    || (first & 0xffc0) == 0xfe80
+            }
+        },
+    }
+}
+
+fn host_ip(url: &Url) -> Option<IpAddr> {
+    url.host_str()?.trim_start_matches('[').trim_end_matches(']').parse().ok()
+}
+
+fn is_forbidden_host(url: &Url) -> bool {
+    match (host_ip(url), url.host_str()) {
+        (Some(ip), _) => is_forbidden_ip(ip),
+        (None, Some(domain)) => {
+            let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+            domain == "localhost" || domain.ends_with(".localhost")
+        }
+        (None, None) => true,
+    }
+}
+
+pub fn is_valid_callback_url(url: &str) -> bool {
+    Url::parse(url)
+        .map(|u| {
+            (u.scheme() == "http" || u.scheme() == "https")
+                && u.host_str().is_some()
+                && (get_webhook_allow_private() || !is_forbidden_host(&u))
+        })
+        .unwrap_or(false)
+}
+
+async fn destination_client(state: &AppState, url: &str, settings: &DeliverySettings) -> Result<reqwest::Client, String> {
+    if settings.allow_private {
+        return Ok(state.http_client.clone());
+    }
+
+    let url = Url::parse(url).map_err(|e| e.to_string())?;
+    if is_forbidden_host(&url) {
+        return Err("callback destination is a private or local address".to_string());
+    }
+    let domain = match (host_ip(&url), url.host_str()) {
+        (None, Some(domain)) => domain,
+        _ => return Ok(state.http_client.clone()),
+    };
+
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = lookup_host((domain, port)).await.map_err(|e| e.to_string())?.collect();
+    if addrs.is_empty() || addrs.iter().any(|addr| is_forbidden_ip(addr.ip())) {
+        return Err("callback destination resolves to a private or local address".to_string());
+    }
+
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(domain, &addrs)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+pub fn register_task_webhooks(state: &AppState, task_id: &str, callback_url: Option<&str>, apikey: Option<&str>) {
+    let urls = callback_url
+        .map(String::from)
+        .into_iter()
+        .chain(apikey.and_then(get_apikey_webhook));
+
+    for url in urls {
+        let mut callbacks = state.task_callbacks.entry(task_id.to_string()).or_default();
+        if !callbacks.contains(&url) {
+            callbacks.push(url);
+        }
+    }
+}
+
+pub fn sign_payload(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={:x}", mac.finalize().into_bytes())
+}
+
+pub fn notify_task_webhooks(state: &web::Data<AppState>, task_id: &str) {
+    let urls = match state.task_callbacks.remove(task_id) {
+        Some((_, urls)) => urls,
+        None => return,
+    };
+    let task = match state.tasks.get(task_id) {
+        Some(task) => task.clone(),
+        None => return,
+    };
+
+    for url in urls {
+        let event = WebhookEvent {
+            id: Uuid::new_v4().to_string(),
+            url,
+            task: task.clone(),
+            attempts: 0,
+            created_at: Utc::now().timestamp_millis(),
+            last_error: String::new(),
+        };
+        spawn_delivery(state, event);
+    }
+}
+
+fn spawn_delivery(state: &web::Data<AppState>, event: WebhookEvent) {
+    let guard = DeliveryGuard::new(state);
+    let state = state.clone();
+    tokio::spawn(async move {
+        deliver_webhook(&state, &DeliverySettings::current(), event, guard).await;
+    });
+}
+
+async fn persist_event(dir: &str, event: &WebhookEvent, suffix: &str) {
+    if let Ok(json) = serde_json::to_vec(event) {
+        let _ = fs::create_dir_all(dir).await;
+        let _ = fs::write(format!("{}/{}{}", dir, event.id, suffix), json).await;
+    }
+}
+
+async fn remove_event(dir: &str, event: &WebhookEvent) {
+    let _ = fs::remove_file(format!("{}/{}.json", dir, event.id)).await;
+}
+
+pub fn retry_backoff(base: Duration, attempts: u32) -> Duration {
+    base.saturating_mul(1 << attempts.saturating_sub(1).min(10)).min(Duration::from_secs(3600))
+}
+
+async fn send_webhook(client: &reqwest::Client, settings: &DeliverySettings, event: &WebhookEvent) -> Result<(), String> {
+    let body = serde_json::to_vec(&event.task).map_err(|e| e.to_string())?;
+    let timestamp = Utc::now().timestamp();
+    let signature = sign_payload(&settings.secret, timestamp, &body);
+
+    let response = client
+        .post(&event.url)
+        .timeout(settings.timeout)
+        .header("content-type", "application/json")
+        .header("x-webhook-id", &event.id)
+        .header("x-webhook-timestamp", timestamp.to_string())
+        .header("x-webhook-signature", signature)
+        .body(body)
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(format!("HTTP {}", response.status().as_u16()))
+    }
+}
+
+pub async fn deliver_webhook(state: &web::Data<AppState>, settings: &DeliverySettings, mut event: WebhookEvent, guard: DeliveryGuard) {
+    if event.attempts == 0 {
+        persist_event(&settings.dir, &event, ".json").await;
+    }
+
+    let mut first = Some(guard);
+    while event.attempts < settings.max_attempts {
+        if event.attempts > 0 {
+            sleep(retry_backoff(settings.backoff_base, event.attempts)).await;
+            if state.shutting_down.load(Ordering::SeqCst) {
+                tracing::info!(webhook_id = %event.id, task_id = %event.task.id, "webhook retry deferred until restart");
+                return;
+            }
+        }
+        let _sending = first.take().unwrap_or_else(|| DeliveryGuard::new(state));
+
+        let client = match destination_client(state, &event.url, settings).await {
+            Ok(client) => client,
+            Err(e) => {
+                tracing::warn!(webhook_id = %event.id, task_id = %event.task.id, error = %e, "webhook destination rejected");
+                event.last_error = e;
+                break;
+            }
+        };
+
+        event.attempts += 1;
+        match send_webhook(&client, settings, &event).await {
+            Ok(()) => {
+                tracing::info!(webhook_id = %event.id, task_id = %event.task.id, attempts = event.attempts, "webhook delivered");
+                remove_event(&settings.dir, &event).await;
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(webhook_id = %event.id, task_id = %event.task.id, attempts = event.attempts, error = %e, "webhook delivery failed");
+                event.last_error = e;
+                persist_event(&settings.dir, &event, ".json").await;
+            }
+        }
+    }
+
+    tracing::error!(webhook_id = %event.id, task_id = %event.task.id, "webhook undeliverable; giving up");
+    remove_event(&settings.dir, &event).await;
+    persist_event(&settings.dir, &event, ".failed.json").await;
+}
+
+pub async fn drain_webhooks(state: &AppState, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+
+    while state.webhook_deliveries.load(Ordering::SeqCst) > 0 {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+
+    true
+}
+
+pub async fn resume_webhooks(state: web::Data<AppState>) {
+    let mut entries = match fs::read_dir(get_webhooks_dir()).await {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !name.ends_with(".json") || name.ends_with(".failed.json") {
+            continue;
+        }
+
+        let event: WebhookEvent = match fs::read(entry.path()).await.ok().and_then(|b| serde_json::from_slice(&b).ok()) {
+            Some(event) => event,
+            None => continue,
+        };
+
+        spawn_delivery(&state, event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Task;
+    use std::path::Path;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    struct Received {
+        headers: Vec<(String, String)>,
+        body: Vec<u8>,
+        persisted: bool,
+    }
+
+    impl Received {
+        fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+        }
+    }
+
+    async fn stand_in(statuses: Vec<u16>, persisted_file: String) -> (String, mpsc::UnboundedReceiver<Received>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let persisted = Path::new(&persisted_file).exists();
+
+                let mut request = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let header_end = loop {
+                    let n = socket.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..n]);
+                    if let Some(index) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break index + 4;
+                    }
+                };
+
+                let head = String::from_utf8_lossy(&request[..header_end]).to_string();
+                let headers: Vec<(String, String)> = head.lines().skip(1)
+                    .filter_map(|line| line.split_once(':'))
+                    .map(|(key, value)| (key.trim().to_ascii_lowercase(), value.trim().to_string()))
+                    .collect();
+                let length: usize = headers.iter()
+                    .find(|(key, _)| key == "content-length")
+                    .and_then(|(_, value)| value.parse().ok())
+                    .unwrap_or(0);
+                while request.len() < header_end + length {
+                    let n = socket.read(&mut chunk).await.unwrap();
+                    request.extend_from_slice(&chunk[..n]);
+                }
+
+                let response = format!("HTTP/1.1 {} Test\r\ncontent-length: 0\r\nconnection: close\r\n\r\n", status);
+                socket.write_all(response.as_bytes()).await.unwrap();
+                let _ = sender.send(Received { headers, body: request[header_end..].to_vec(), persisted });
+            }
+        });
+
+        (url, receiver)
+    }
+
+    fn test_state() -> web::Data<AppState> {
+        web::Data::new(AppState::new(1, "ffmpeg".to_string(), "ffprobe".to_string()))
+    }
+
+    fn test_settings(dir: &str, max_attempts: u32) -> DeliverySettings {
+        DeliverySettings {
+            secret: "secret".to_string(),
+            max_attempts,
+            timeout: Duration::from_secs(5),
+            dir: dir.to_string(),
+            backoff_base: Duration::from_millis(10),
+            allow_private: true,
+        }
+    }
+
+    fn test_dir() -> String {
+        std::env::temp_dir().join(format!("webhook-test-{}", Uuid::new_v4())).to_string_lossy().to_string()
+    }
+
+    fn test_event(url: &str) -> WebhookEvent {
+        WebhookEvent {
+            id: Uuid::new_v4().to_string(),
+            url: url.to_string(),
+            task: Task { id: "t1".to_string(), status: "completed".to_string(), ..Default::default() },
+            attempts: 0,
+            created_at: 0,
+            last_error: String::new(),
+        }
+    }
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        assert_eq!(
+            sign_payload("secret", 1700000000, br#"{"id":"t1"}"#),
+            "sha256=9595e6d0617566cdbe839b4f114ce172720e69818f5e70ed5407254a0afe28a1"
+        );
+    }
+
+    #[test]
+    fn backoff_doubles_and_caps() {
+        let base = Duration::from_secs(5);
+        assert_eq!(retry_backoff(base, 1), Duration::from_secs(5));
+        assert_eq!(retry_backoff(base, 2), Duration::from_secs(10));
+        assert_eq!(retry_backoff(base, 4), Duration::from_secs(40));
+        assert_eq!(retry_backoff(base, 30), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn rejects_local_and_private_destinations() {
+        for ip in ["127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0", "::1", "fe80::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(is_forbidden_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "8.8.8.8", "2606:4700::1111"] {
+            assert!(!is_forbidden_ip(ip.parse().unwrap()), "{}", ip);
+        }
+
+        assert!(!is_valid_callback_url("http://169.254.169.254/latest/meta-data"));
+        assert!(!is_valid_callback_url("http://[::1]:8080/hook"));
+        assert!(!is_valid_callback_url("http://localhost/hook"));
+        assert!(!is_valid_callback_url("ftp://example.com/hook"));
+        assert!(is_valid_callback_url("https://example.com/hook"));
+    }
+
+    #[tokio::test]
+    async fn retries_with_signature_until_delivered() {
+        let dir = test_dir();
+        let event = test_event("");
+        let event_file = format!("{}/{}.json", dir, event.id);
+        let (url, mut received) = stand_in(vec![500, 200], event_file.clone()).await;
+        let event = WebhookEvent { url, ..event };
+        let state = test_state();
+
+        deliver_webhook(&state, &test_settings(&dir, 3), event, DeliveryGuard::new(&state)).await;
+
+        let first = received.recv().await.unwrap();
+        let second = received.recv().await.unwrap();
+        assert!(first.persisted, "event must be on disk before the first attempt");
+        assert_eq!(first.body, second.body);
+
+        let timestamp: i64 = second.header("x-webhook-timestamp").unwrap().parse().unwrap();
+        assert_eq!(second.header("x-webhook-signature").unwrap(), sign_payload("secret", timestamp, &second.body));
+        assert_eq!(serde_json::from_slice::<Task>(&second.body).unwrap().id, "t1");
+
+        assert!(!Path::new(&event_file).exists());
+        assert_eq!(state.webhook_deliveries.load(Ordering::SeqCst), 0);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn keeps_undeliverable_events_on_disk() {
+        let dir = test_dir();
+        let event = test_event("");
+        let (url, mut received) = stand_in(vec![503, 503], String::new()).await;
+        let event = WebhookEvent { url, ..event };
+        let id = event.id.clone();
+        let state = test_state();
+
+        deliver_webhook(&state, &test_settings(&dir, 2), event, DeliveryGuard::new(&state)).await;
+
+        assert!(received.recv().await.is_some());
+        assert!(received.recv().await.is_some());
+        assert!(!Path::new(&format!("{}/{}.json", dir, id)).exists());
+
+        let failed: WebhookEvent = serde_json::from_slice(&std::fs::read(format!("{}/{}.failed.json", dir, id)).unwrap()).unwrap();
+        assert_eq!(failed.attempts, 2);
+        assert_eq!(failed.last_error, "HTTP 503");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn refuses_private_destinations_without_sending() {
+        let dir = test_dir();
+        let (url, mut received) = stand_in(vec![200], String::new()).await;
+        let event = test_event(&url);
+        let id = event.id.clone();
+        let state = test_state();
+        let settings = DeliverySettings { allow_private: false, ..test_settings(&dir, 3) };
+
+        deliver_webhook(&state, &settings, event, DeliveryGuard::new(&state)).await;
+
+        assert!(received.try_recv().is_err());
+        assert!(Path::new(&format!("{}/{}.failed.json", dir, id)).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -11,6 +11,21 @@ pub fn get_client_identifier(ip: &str, user_agent: &str, url: &str, media_type:
     format!("{:x}", hasher.finalize())
 }
 
+pub fn apikey_owner(apikey: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(apikey.as_bytes()));
+    format!("key_{}", &hash[..16])
+}
+
+pub fn session_owner(session_token: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(session_token.as_bytes()));
+    format!("session_{}", &hash[..16])
+}
+
+pub fn ip_owner(ip: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(ip.as_bytes()));
+    format!("ip_{}", &hash[..16])
+}
+
 pub fn generate_challenge() -> String {
     let mut hasher = Sha256::new();
     hasher.update(Uuid::new_v4().to_string().as_bytes());
@@ -28,9 +43,9 @@ pub fn verify_pow(challenge: &str, nonce: &str, difficulty: usize) -> bool {
     hash.starts_with(&prefix)
 }
 
-pub fn generate_session_token(task_id: &str, ip: &str, user_agent: &str) -> String {
+pub fn generate_session_token(content_key: &str, ip: &str, user_agent: &str) -> String {
     let mut hasher = Sha256::new();
-    hasher.update(task_id.as_bytes());
+    hasher.update(content_key.as_bytes());
     hasher.update(ip.as_bytes());
     hasher.update(user_agent.as_bytes());
     hasher.update(Utc::now().timestamp_millis().to_string().as_bytes());
@@ -1,7 +1,6 @@
 use actix_web::HttpRequest;
 use std::path::PathBuf;
 use std::fs;
-use rand::Rng;
 use crate::config::{get_cookies_dir, use_cookies};
 
 pub fn get_real_ip(req: &HttpRequest) -> String {
@@ -46,7 +45,7 @@ pub fn get_available_cookies() -> Vec<String> {
             if let Ok(metadata) = entry.metadata() {
                 if metadata.is_file() {
                     if let Some(filename) = entry.file_name().to_str() {
-                        if filename.starts_with("cookies") && filename.ends_with(".txt") {
+                        if is_cookie_filename(filename) {
                             if let Ok(path) = entry.path().canonicalize() {
                                 cookie_files.push(path.to_string_lossy().to_string());
                             }
@@ -61,20 +60,40 @@ pub fn get_available_cookies() -> Vec<String> {
     cookie_files
 }
 
-pub fn get_random_cookies() -> Option<String> {
-    let available = get_available_cookies();
-    
-    if available.is_empty() {
-        return None;
+pub fn is_safe_filename(filename: &str) -> bool {
+    !filename.contains("..") && !filename.contains('/') && !filename.contains('\\')
+}
+
+pub fn is_cookie_filename(filename: &str) -> bool {
+    is_safe_filename(filename) && filename.starts_with("cookies") && filename.ends_with(".txt")
+}
+
+pub fn redact_proxy(proxy: &str) -> String {
+    match (proxy.find("://"), proxy.rfind('@')) {
+        (Some(scheme), Some(at)) if at > scheme => format!("{}***{}", &proxy[..scheme + 3], &proxy[at..]),
+        (None, Some(at)) => format!("***{}", &proxy[at..]),
+        _ => proxy.to_string(),
     }
-    
-    let mut rng = rand::thread_rng();
-    let index = rng.gen_range(0..available.len());
-    Some(available[index].clone())
 }
 
-pub fn is_safe_filename(filename: &str) -> bool {
-    !filename.contains("..") && !filename.contains('/') && !filename.contains('\\')
+pub async fn dir_size(dir: &str) -> u64 {
+    let mut total = 0;
+    let mut pending = vec![PathBuf::from(dir)];
+
+    while let Some(dir) = pending.pop() {
+        let Ok(mut entries) = tokio::fs::read_dir(&dir).await else {
+            continue;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            match entry.metadata().await {
+                Ok(metadata) if metadata.is_dir() => pending.push(entry.path()),
+                Ok(metadata) if metadata.is_file() => total += metadata.len(),
+                _ => {}
+            }
+        }
+    }
+
+    total
 }
 
 pub fn find_executable(name: &str) -> String {
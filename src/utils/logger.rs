@@ -1,21 +1,34 @@
-use chrono::Local;
+use tracing_subscriber::EnvFilter;
+use crate::config::{get_log_format, get_log_level};
 
-pub fn log_request(method: &str, path: &str, status: u16, latency_ms: u128) {
-    println!(
-        "[{}] {} {} - {} ({}ms)",
-        Local::now().format("%H:%M:%S"),
-        method,
-        path,
-        status,
-        latency_ms
-    );
+pub fn init_logging() {
+    let filter = EnvFilter::try_new(get_log_level()).unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter).with_target(false);
+
+    match get_log_format().as_str() {
+        "pretty" => builder.pretty().init(),
+        _ => builder.json().flatten_event(true).with_current_span(true).with_span_list(false).init(),
+    }
+}
+
+pub fn init_cli_logging() {
+    let filter = EnvFilter::try_new(get_log_level()).unwrap_or_else(|_| EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
+}
+
+pub fn log_request(request_id: &str, method: &str, path: &str, status: u16, latency_ms: u128) {
+    tracing::info!(request_id, method, path, status, latency_ms = latency_ms as u64, "request");
 }
 
 #[allow(dead_code)]
 pub fn log_error(msg: &str) {
-    println!("[{}] ERROR: {}", Local::now().format("%H:%M:%S"), msg);
+    tracing::error!("{}", msg);
 }
 
 pub fn log_startup(msg: &str) {
-    println!("{}", msg);
+    tracing::info!("{}", msg);
 }